@@ -0,0 +1,232 @@
+use crate::geometry::{Anchor, Length, Struts};
+use serde_derive::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+
+const ENV_PREFIX: &str = "DERPY_WM";
+
+/// How the pointer focuses windows.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusMode {
+    Click,
+    FollowsMouse,
+}
+
+/// `[[scratchpad]]` that `SP_TOGGLE` launches when it has no window yet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScratchpadConfig {
+    pub name: String,
+    /// Shell command that starts the program
+    pub command: String,
+    /// `WM_CLASS` instance or class name of the window to capture
+    pub class: String,
+    /// Defaults to `scratchpad_size`
+    #[serde(default)]
+    pub size: Option<(Length, Length)>,
+    #[serde(default)]
+    pub anchor: Anchor,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub workspaces: usize,
+    pub gaps: usize,
+    pub focused_border: String,
+    pub unfocused_border: String,
+    pub urgent_border: String,
+    /// Minimum reserved space, docks can reserve more with `_NET_WM_STRUT_PARTIAL`
+    pub reserved: Struts,
+    /// Bottom panel height from before `reserved`, read into `reserved.bottom`
+    pub panel_width: Option<usize>,
+    pub scratchpad_size: (usize, usize),
+    // TODO read from xrandr
+    pub workspace_size: (usize, usize),
+    pub focus_mode: FocusMode,
+    /// How long `WINDOW_CLOSE` waits for a window that doesn't answer pings before killing its client
+    pub kill_timeout_ms: u64,
+    /// Unix socket for commands that need an answer, see `socket::default_socket_path`
+    pub socket: Option<PathBuf>,
+    #[serde(rename = "scratchpad")]
+    pub scratchpads: Vec<ScratchpadConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            workspaces: 8,
+            gaps: 10,
+            focused_border: "0xff0000".to_string(),
+            unfocused_border: "0x888888".to_string(),
+            urgent_border: "0xffa500".to_string(),
+            reserved: Struts {
+                bottom: 18,
+                ..Struts::default()
+            },
+            panel_width: None,
+            scratchpad_size: (1200, 700),
+            workspace_size: (1920, 1080),
+            focus_mode: FocusMode::Click,
+            kill_timeout_ms: 3000,
+            socket: None,
+            scratchpads: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Load(::config::ConfigError),
+    Invalid { key: &'static str, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Load(err) => write!(f, "can't load config: {}", err),
+            ConfigError::Invalid { key, message } => {
+                write!(f, "invalid value for `{}`: {}", key, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<::config::ConfigError> for ConfigError {
+    fn from(err: ::config::ConfigError) -> ConfigError {
+        ConfigError::Load(err)
+    }
+}
+
+fn invalid(key: &'static str, message: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid {
+        key,
+        message: message.into(),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/derpy-wm/config.toml`, falling back to `~/.config`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("derpy-wm").join("config.toml"))
+}
+
+impl Config {
+    pub fn scratchpad(&self, name: &str) -> Option<&ScratchpadConfig> {
+        self.scratchpads
+            .iter()
+            .find(|scratchpad| scratchpad.name == name)
+    }
+
+    /// Layers defaults, the config file and `DERPY_WM_*` environment variables,
+    /// then validates the result. An explicitly given file must exist. Nested keys
+    /// are separated by `__` in variable names, `DERPY_WM_RESERVED__TOP` sets `reserved.top`.
+    pub fn load(path: Option<PathBuf>) -> Result<Config, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (Some(path), true),
+            None => (default_config_path(), false),
+        };
+
+        let mut settings = ::config::Config::new();
+        if let Some(path) = path {
+            settings.merge(
+                ::config::File::new(&path.to_string_lossy(), ::config::FileFormat::Toml)
+                    .required(required),
+            )?;
+        }
+        settings.merge(::config::Environment::with_prefix(ENV_PREFIX).separator("__"))?;
+
+        let mut config: Config = settings.try_into()?;
+        if let Some(panel_width) = config.panel_width.take() {
+            config.reserved.bottom = panel_width;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.workspaces == 0 {
+            return Err(invalid("workspaces", "at least one workspace is required"));
+        }
+
+        let (wsw, wsh) = self.workspace_size;
+        if wsw == 0 || wsh == 0 {
+            return Err(invalid(
+                "workspace_size",
+                format!("{}x{} has no area", wsw, wsh),
+            ));
+        }
+        let reserved = &self.reserved;
+        if reserved.left.saturating_add(reserved.right) >= wsw
+            || reserved.top.saturating_add(reserved.bottom) >= wsh
+        {
+            return Err(invalid(
+                "reserved",
+                format!("{:?} doesn't fit into {}x{}", reserved, wsw, wsh),
+            ));
+        }
+        // Two columns need three gaps horizontally, a single row needs two vertically
+        let free_w = wsw - reserved.left - reserved.right;
+        let free_h = wsh - reserved.top - reserved.bottom;
+        if self.gaps.saturating_mul(3) >= free_w || self.gaps.saturating_mul(2) >= free_h {
+            return Err(invalid(
+                "gaps",
+                format!(
+                    "{} leaves no room for windows on {}x{}",
+                    self.gaps, free_w, free_h
+                ),
+            ));
+        }
+
+        let (spw, sph) = self.scratchpad_size;
+        if spw == 0 || sph == 0 || spw > wsw || sph > wsh {
+            return Err(invalid(
+                "scratchpad_size",
+                format!("{}x{} doesn't fit into {}x{}", spw, sph, wsw, wsh),
+            ));
+        }
+
+        for (i, scratchpad) in self.scratchpads.iter().enumerate() {
+            if scratchpad.name.is_empty()
+                || scratchpad.command.is_empty()
+                || scratchpad.class.is_empty()
+            {
+                return Err(invalid(
+                    "scratchpad",
+                    "name, command and class can't be empty",
+                ));
+            }
+            if self.scratchpads[..i]
+                .iter()
+                .any(|other| other.name == scratchpad.name)
+            {
+                return Err(invalid(
+                    "scratchpad",
+                    format!("`{}` is defined twice", scratchpad.name),
+                ));
+            }
+        }
+
+        check_color("focused_border", &self.focused_border)?;
+        check_color("unfocused_border", &self.unfocused_border)?;
+        check_color("urgent_border", &self.urgent_border)?;
+        Ok(())
+    }
+}
+
+/// Colors are passed to `chwb -c`, which expects `0xRRGGBB` or `RRGGBB`.
+fn check_color(key: &'static str, color: &str) -> Result<(), ConfigError> {
+    let digits = color.strip_prefix("0x").unwrap_or(color);
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid(
+            key,
+            format!("`{}` is not a hex color like 0xff0000", color),
+        ));
+    }
+    Ok(())
+}
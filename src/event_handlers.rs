@@ -1,254 +1,936 @@
-use crate::reconciler::{WMState, WorkspaceState};
-use ::derpywm::{is_ignored, Event, ScratchpadEvent, WindowEvent, WindowEventType, WorkspaceEvent};
+use crate::mutation::{Mutation, Transaction};
+use crate::processor::Backend;
+use crate::reconciler::{Drag, DragKind, WMState, WorkspaceState};
+use crate::{
+    ClientEvent, Config, Direction, PointerEvent, QueryEvent, ScratchpadEvent, StateAction,
+    WindowCommand, WindowEvent, WindowEventType, WindowId, WorkspaceEvent,
+};
+use std::fmt;
 
-pub fn handle_scratchpad_event(now: &mut WMState, event: ScratchpadEvent) -> bool {
+/// Resized rows and columns never get thinner than this, relative to
+/// `DEFAULT_WEIGHT` and thousandths of the width respectively.
+const MIN_WEIGHT: i64 = 10;
+const MIN_COLUMN_SHARE: i64 = 100;
+
+/// Why an event was rejected, its transaction should be rolled back.
+#[derive(Debug)]
+pub enum HandlerError {
+    NoSuchWorkspace(usize),
+    NoSuchScratchpad(String),
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HandlerError::NoSuchWorkspace(ws) => write!(f, "there is no workspace {}", ws + 1),
+            HandlerError::NoSuchScratchpad(name) => {
+                write!(f, "there is no scratchpad `{}`", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HandlerError {}
+
+pub fn handle_scratchpad_event(
+    now: &mut Transaction,
+    event: ScratchpadEvent,
+    backend: &mut dyn Backend,
+    config: &Config,
+) -> Result<bool, HandlerError> {
     match event {
         ScratchpadEvent::AddWindow(name) => {
-            if let Some(focused_wid) = &now.focused_window {
-                remove_window_from_workspace(
-                    &mut now.workspaces[now.focused_workspace],
-                    focused_wid.as_str(),
-                );
-                now.scratchpad.windows.push((name, focused_wid.to_string()));
+            // Only workspace windows can become scratchpads
+            let (ws, wid) = match now.focused_window {
+                Some(wid) => match workspace_of_window(now, wid) {
+                    Some(ws) => (ws, wid),
+                    None => return Ok(false),
+                },
+                None => return Ok(false),
+            };
+            remove_window_from_workspace(now, ws, wid);
+            // Reusing a name returns its old window to the workspace
+            if let Some(old_wid) = now.scratchpad.window(&name) {
+                now.apply(Mutation::RemoveScratchpad {
+                    name: name.clone(),
+                    window: old_wid,
+                });
+                let ws = now.focused_workspace;
+                add_window_to_workspace(now, ws, old_wid);
             }
+            if now.scratchpad.pending.contains(&name) {
+                now.apply(Mutation::Pending {
+                    name: name.clone(),
+                    pending: false,
+                });
+            }
+            now.apply(Mutation::AddScratchpad { name, window: wid });
         }
         ScratchpadEvent::RemoveWindow(name) => {
-            if let Some((_, wid)) = now
-                .scratchpad
-                .windows
-                .iter()
-                .find(|(wname, _)| wname == name.as_str())
-            {
-                add_window_to_workspace(&mut now.workspaces[now.focused_workspace], wid.as_str());
-                now.scratchpad
-                    .windows
-                    .retain(|(wname, _)| wname != name.as_str());
-            }
-            if let Some(shown) = &now.scratchpad.shown.as_ref() {
-                if shown == &name.as_str() {
-                    now.scratchpad.shown = None
-                }
-            }
+            let wid = match now.scratchpad.window(&name) {
+                Some(wid) => wid,
+                None => return Err(HandlerError::NoSuchScratchpad(name)),
+            };
+            now.apply(Mutation::RemoveScratchpad { name, window: wid });
+            let ws = now.focused_workspace;
+            add_window_to_workspace(now, ws, wid);
         }
         ScratchpadEvent::ToggleWindow(name) => {
-            if now
-                .scratchpad
-                .windows
-                .iter()
-                .find(|(wname, _)| wname == name.as_str())
-                .is_none()
-            {
-                return false;
-            }
-            if let Some(wid) = &now.scratchpad.shown {
-                // Reset fullscreen if it's current scratchpad window
-                if let Some(fullscreen) = &now.workspaces[now.focused_workspace].fullscreen {
-                    if fullscreen == wid.as_str() {
-                        now.workspaces[now.focused_workspace].fullscreen = None;
+            let wid = match now.scratchpad.window(&name) {
+                Some(wid) => wid,
+                None => {
+                    // Launch it, the window is captured when it maps
+                    let scratchpad = match config.scratchpad(&name) {
+                        Some(scratchpad) => scratchpad,
+                        None => return Err(HandlerError::NoSuchScratchpad(name)),
+                    };
+                    // Toggling again before it maps gives up waiting, in case it never
+                    // will, so the next toggle launches it anew
+                    if now.scratchpad.pending.contains(&name) {
+                        now.apply(Mutation::Pending {
+                            name,
+                            pending: false,
+                        });
+                        return Ok(true);
                     }
+                    backend.spawn(scratchpad.command.as_str());
+                    now.apply(Mutation::Pending {
+                        name,
+                        pending: true,
+                    });
+                    return Ok(true);
                 }
-                if wid == name.as_str() {
-                    now.scratchpad.shown = None;
-                } else {
-                    now.scratchpad.shown = Some(name);
-                }
-            } else {
-                now.scratchpad.shown = Some(name);
+            };
+            toggle_scratchpad(now, name, wid);
+        }
+        ScratchpadEvent::RestoreAll => {
+            if now.scratchpad.windows.is_empty() {
+                return Ok(false);
+            }
+            let ws = now.focused_workspace;
+            for (name, wid) in now.scratchpad.windows.clone() {
+                now.apply(Mutation::RemoveScratchpad { name, window: wid });
+                add_window_to_workspace(now, ws, wid);
+            }
+        }
+        ScratchpadEvent::Resize(name, size, anchor) => {
+            let known = now.scratchpad.window(&name).is_some();
+            if !known && config.scratchpad(&name).is_none() {
+                return Err(HandlerError::NoSuchScratchpad(name));
+            }
+            let mut geometry = now.scratchpad.geometry(&name, config);
+            geometry.size = size;
+            geometry.anchor = anchor.unwrap_or(geometry.anchor);
+            now.apply(Mutation::ScratchpadGeometry { name, geometry });
+        }
+    }
+    Ok(true)
+}
+
+pub fn handle_client_event(
+    now: &mut Transaction,
+    event: ClientEvent,
+    backend: &mut dyn Backend,
+    config: &Config,
+) -> Result<bool, HandlerError> {
+    match event {
+        ClientEvent::CurrentDesktop(ws) => {
+            handle_workspace_event(now, WorkspaceEvent::Focus(ws), config)
+        }
+        ClientEvent::ActiveWindow(wid) => Ok(activate_window(now, wid)),
+        ClientEvent::WindowDesktop(wid, ws) => {
+            check_workspace(now, ws)?;
+            Ok(move_window_to_workspace(now, wid, ws))
+        }
+        ClientEvent::CloseWindow(wid) => {
+            backend.close_window(wid, config);
+            Ok(false)
+        }
+        ClientEvent::Fullscreen(wid, action) => {
+            // Shown scratchpads go fullscreen on the focused workspace like with WS_FULLSCREEN
+            let shown_scratchpad = now.scratchpad.shown_windows().contains(&wid);
+            let ws = match workspace_of_window(now, wid) {
+                Some(ws) => ws,
+                None if shown_scratchpad => now.focused_workspace,
+                None => return Ok(false),
+            };
+            let is_fullscreen = now.workspaces[ws].fullscreen == Some(wid);
+            let enable = match action {
+                StateAction::Add => true,
+                StateAction::Remove => false,
+                StateAction::Toggle => !is_fullscreen,
+            };
+            if enable == is_fullscreen {
+                return Ok(false);
+            }
+            now.set_fullscreen(ws, if enable { Some(wid) } else { None });
+            Ok(true)
+        }
+        ClientEvent::Attention(wid, action) => {
+            let is_urgent = now.urgent.contains(&wid);
+            let urgent = match action {
+                StateAction::Add => true,
+                StateAction::Remove => false,
+                StateAction::Toggle => !is_urgent,
+            };
+            let managed = now.window_desktops().iter().any(|(mwid, _)| *mwid == wid);
+            if urgent == is_urgent || (urgent && (!managed || now.focused_window == Some(wid))) {
+                return Ok(false);
+            }
+            now.apply(Mutation::Urgent {
+                window: wid,
+                urgent,
+            });
+            Ok(true)
+        }
+        ClientEvent::Struts(wid, struts) => {
+            // Only unmanaged docks reserve space
+            let current = now
+                .dock_struts
+                .iter()
+                .find(|(dock, _)| *dock == wid)
+                .map(|(_, struts)| *struts);
+            if now.manages(wid) || current == struts {
+                return Ok(false);
             }
+            now.apply(Mutation::DockStruts {
+                window: wid,
+                struts,
+            });
+            Ok(true)
         }
     }
-    true
 }
 
-pub fn handle_workspace_event(now: &mut WMState, event: WorkspaceEvent) -> bool {
+pub fn handle_workspace_event(
+    now: &mut Transaction,
+    event: WorkspaceEvent,
+    config: &Config,
+) -> Result<bool, HandlerError> {
     match event {
         WorkspaceEvent::Focus(ws) => {
+            check_workspace(now, ws)?;
             if now.focused_workspace == ws {
-                return false;
+                return Ok(false);
             }
-            now.focused_workspace = ws;
+            switch_workspace(now, ws);
         }
         WorkspaceEvent::MoveWindow(ws) => {
+            check_workspace(now, ws)?;
             if now.focused_workspace == ws {
-                return false;
-            }
-            if let Some(focused_wid) = &now.focused_window {
-                remove_window_from_workspace(
-                    &mut now.workspaces[now.focused_workspace],
-                    focused_wid.as_str(),
-                );
-                add_window_to_workspace(&mut now.workspaces[ws], focused_wid.as_str());
+                return Ok(false);
+            }
+            if let Some(focused_wid) = now.focused_window {
+                return Ok(move_window_to_workspace(now, focused_wid, ws));
             } else {
-                return false;
+                return Ok(false);
             }
         }
         WorkspaceEvent::FullscreenToggle => {
-            if now.workspaces[now.focused_workspace].fullscreen.is_some() {
-                now.workspaces[now.focused_workspace].fullscreen = None;
-            } else if let Some(focused_wid) = &now.focused_window {
-                now.workspaces[now.focused_workspace].fullscreen = Some(focused_wid.clone());
+            let ws = now.focused_workspace;
+            let workspace = &now.workspaces[ws];
+            if workspace.fullscreen.is_some() {
+                now.set_fullscreen(ws, None);
+            } else if let Some(focused_wid) = now.focused_window {
+                // Only windows of this workspace and scratchpads shown over it,
+                // sticky windows are on every workspace and stay floating
+                if !workspace.windows.contains(&focused_wid)
+                    && !now.scratchpad.shown_windows().contains(&focused_wid)
+                {
+                    return Ok(false);
+                }
+                now.set_fullscreen(ws, Some(focused_wid));
+            }
+        }
+        WorkspaceEvent::FocusUrgent => {
+            if let Some(wid) = now.urgent.last().copied() {
+                return Ok(activate_window(now, wid));
             }
+            return Ok(false);
+        }
+        WorkspaceEvent::FocusNext | WorkspaceEvent::FocusPrev | WorkspaceEvent::FocusLast => {
+            if !now.scratchpad.shown.is_empty() {
+                return Ok(false);
+            }
+            let ws = now.focused_workspace;
+            let workspace = &now.workspaces[ws];
+            let n = workspace.windows.len();
+            let target = match (event, focused_index(workspace, &workspace.windows)) {
+                (_, None) => None,
+                (_, Some(_)) if n < 2 => None,
+                (WorkspaceEvent::FocusNext, Some(i)) => Some(workspace.windows[(i + 1) % n]),
+                (WorkspaceEvent::FocusPrev, Some(i)) => Some(workspace.windows[(i + n - 1) % n]),
+                _ => workspace.focus_history.iter().rev().nth(1).copied(),
+            };
+            let target = match target {
+                Some(target) => target,
+                None => return Ok(false),
+            };
+            // Fullscreen follows focus, so cycling works like a monocle layout
+            if workspace.fullscreen.is_some() {
+                now.set_fullscreen(ws, Some(target));
+            }
+            focus_window_on_workspace(now, ws, target);
         }
         WorkspaceEvent::Cycle => {
-            let windows = &mut now.workspaces[now.focused_workspace].windows;
-            if !windows.is_empty() {
+            let ws = now.focused_workspace;
+            let mut windows = now.workspaces[ws].windows.clone();
+            if windows.len() > 1 {
                 windows.rotate_right(1);
+                now.apply(Mutation::OrderWindows {
+                    workspace: ws,
+                    windows,
+                });
             }
         }
         WorkspaceEvent::FocusWindow(direction) => {
             if now.workspaces[now.focused_workspace].fullscreen.is_some()
-                || now.scratchpad.shown.is_some()
+                || !now.scratchpad.shown.is_empty()
             {
-                return false;
+                return Ok(false);
             }
-            let windows = &now.workspaces[now.focused_workspace].windows;
-            if windows.len() < 2 {
-                return false;
+            let ws = now.focused_workspace;
+            let workspace = &now.workspaces[ws];
+            let tiled = workspace.tiled_windows();
+            // Focus stays on the edge window, crossing to another output isn't supported yet
+            match focused_index(workspace, &tiled)
+                .and_then(|focused| neighbour_index(tiled.len(), focused, direction))
+            {
+                Some(neighbour) => focus_window_on_workspace(now, ws, tiled[neighbour]),
+                None => return Ok(false),
             }
-            let n = windows.len();
-            let left_n = n / 2;
-            let right_n = n - left_n;
-            let focused_wid = now.workspaces[now.focused_workspace]
-                .focus_history
-                .iter()
-                .last()
-                .unwrap()
-                .clone();
-            let focused_index = windows
-                .iter()
-                .position(|wid| wid == focused_wid.as_str())
-                .unwrap();
-            match direction.as_str() {
-                "LEFT" => {
-                    if focused_index >= left_n {
-                        let i = if right_n > focused_index {
-                            0
-                        } else {
-                            focused_index - right_n
-                        };
-                        let prev_window = now.workspaces[now.focused_workspace].windows[i].clone();
-                        focus_window_on_workspace(
-                            &mut now.workspaces[now.focused_workspace],
-                            prev_window,
-                        );
-                    }
+        }
+        WorkspaceEvent::Swap(direction) => {
+            if now.workspaces[now.focused_workspace].fullscreen.is_some()
+                || !now.scratchpad.shown.is_empty()
+            {
+                return Ok(false);
+            }
+            let ws = now.focused_workspace;
+            let workspace = &now.workspaces[ws];
+            let tiled = workspace.tiled_windows();
+            let neighbour = focused_index(workspace, &tiled).and_then(|focused| {
+                Some((focused, neighbour_index(tiled.len(), focused, direction)?))
+            });
+            match neighbour {
+                Some((focused, neighbour)) => {
+                    swap_windows(now, ws, tiled[focused], tiled[neighbour])
                 }
-                "RIGHT" => {
-                    if focused_index < left_n {
-                        let prev_window = now.workspaces[now.focused_workspace].windows
-                            [focused_index + left_n]
-                            .clone();
-                        focus_window_on_workspace(
-                            &mut now.workspaces[now.focused_workspace],
-                            prev_window,
-                        );
+                None => return Ok(false),
+            }
+        }
+        WorkspaceEvent::Resize(direction, px) => {
+            if now.workspaces[now.focused_workspace].fullscreen.is_some()
+                || !now.scratchpad.shown.is_empty()
+            {
+                return Ok(false);
+            }
+            let rects = now.tiled_rects(config);
+            let ws = now.focused_workspace;
+            let workspace = &now.workspaces[ws];
+            let tiled = workspace.tiled_windows();
+            let (focused, neighbour) = match focused_index(workspace, &tiled).and_then(|focused| {
+                Some((focused, neighbour_index(tiled.len(), focused, direction)?))
+            }) {
+                Some(indices) => indices,
+                None => return Ok(false),
+            };
+            let rect = rects[focused].1;
+            match direction {
+                Direction::Left | Direction::Right => {
+                    // The focused window's column grows, the other one shrinks
+                    let in_left = focused < neighbour;
+                    let share = i64::from(workspace.column_share);
+                    let column = if in_left { share } else { 1000 - share };
+                    let delta = i64::from(px) * column / i64::from(rect.w.max(1));
+                    let share = if in_left {
+                        share + delta
+                    } else {
+                        share - delta
+                    };
+                    let share = share.clamp(MIN_COLUMN_SHARE, 1000 - MIN_COLUMN_SHARE) as u32;
+                    if share == workspace.column_share {
+                        return Ok(false);
                     }
+                    now.apply(Mutation::ColumnShare {
+                        workspace: ws,
+                        share,
+                    });
                 }
-                "UP" => {
-                    if focused_index != 0 && focused_index != left_n {
-                        let prev_window = now.workspaces[now.focused_workspace].windows
-                            [focused_index - 1]
-                            .clone();
-                        focus_window_on_workspace(
-                            &mut now.workspaces[now.focused_workspace],
-                            prev_window,
-                        );
+                Direction::Up | Direction::Down => {
+                    // Rows trade weight, keeping the column's total unchanged
+                    let weight = i64::from(workspace.weight(tiled[focused]));
+                    let other = i64::from(workspace.weight(tiled[neighbour]));
+                    // Rows already below the minimum may stay there, but not shrink further
+                    let low = (MIN_WEIGHT - weight).min(0);
+                    let high = (other - MIN_WEIGHT).max(0);
+                    let delta =
+                        (i64::from(px) * weight / i64::from(rect.h.max(1))).clamp(low, high);
+                    if delta == 0 {
+                        return Ok(false);
                     }
+                    now.apply(Mutation::Weight {
+                        workspace: ws,
+                        window: tiled[focused],
+                        weight: (weight + delta) as u32,
+                    });
+                    now.apply(Mutation::Weight {
+                        workspace: ws,
+                        window: tiled[neighbour],
+                        weight: (other - delta) as u32,
+                    });
                 }
-                "DOWN" => {
-                    if focused_index != (left_n - 1) && focused_index != (n - 1) {
-                        let prev_window = now.workspaces[now.focused_workspace].windows
-                            [focused_index + 1]
-                            .clone();
-                        focus_window_on_workspace(
-                            &mut now.workspaces[now.focused_workspace],
-                            prev_window,
-                        );
-                    }
+            }
+        }
+        WorkspaceEvent::Promote => {
+            if now.workspaces[now.focused_workspace].fullscreen.is_some()
+                || !now.scratchpad.shown.is_empty()
+            {
+                return Ok(false);
+            }
+            let ws = now.focused_workspace;
+            let workspace = &now.workspaces[ws];
+            let tiled = workspace.tiled_windows();
+            match focused_index(workspace, &tiled) {
+                Some(focused) if focused != 0 => {
+                    let mut windows = workspace.windows.clone();
+                    windows.retain(|wid| *wid != tiled[focused]);
+                    windows.insert(0, tiled[focused]);
+                    now.apply(Mutation::OrderWindows {
+                        workspace: ws,
+                        windows,
+                    });
                 }
-                _ => {}
+                _ => return Ok(false),
             }
         }
     }
-    true
+    Ok(true)
 }
 
-pub fn handle_window_event(now: &mut WMState, event: WindowEvent, last_event: &Event) -> bool {
+pub fn handle_window_event(
+    now: &mut Transaction,
+    event: WindowEvent,
+    backend: &mut dyn Backend,
+    config: &Config,
+) -> Result<bool, HandlerError> {
+    let wid = event.window_id;
     match event.event_type {
-        WindowEventType::CreateNotify => {}
-        WindowEventType::MapNotify => {
-            if let Event::Window(last_event) = &last_event {
-                if last_event.event_type == WindowEventType::CreateNotify {
-                    if is_ignored(event.window_id.as_str()) {
-                        return false;
-                    }
-                    add_window_to_workspace(
-                        &mut now.workspaces[now.focused_workspace],
-                        event.window_id.as_str(),
-                    );
-                }
+        WindowEventType::CreateNotify => {
+            if now.manages(wid) || now.created.contains(&wid) {
+                return Ok(false);
             }
+            now.apply(Mutation::Created {
+                window: wid,
+                created: true,
+            });
         }
-        WindowEventType::DestroyNotify => {
-            for workspace in &mut now.workspaces {
-                remove_window_from_workspace(workspace, event.window_id.as_str());
+        WindowEventType::MapNotify => {
+            // Only the first map after creation manages a window, whatever came in between
+            if !now.created.contains(&wid) {
+                return Ok(false);
             }
-            if let Some(shown) = &now.scratchpad.shown {
-                let (_, shown_wid) = now
-                    .scratchpad
-                    .windows
-                    .iter()
-                    .find(|(name, _)| name == shown.as_str())
-                    .unwrap();
-                if shown_wid == event.window_id.as_str() {
-                    now.scratchpad.shown = None;
+            now.apply(Mutation::Created {
+                window: wid,
+                created: false,
+            });
+            if backend.is_ignored(wid) {
+                // Docks aren't managed, but their struts are respected
+                if let Some(struts) = backend.window_struts(wid) {
+                    now.apply(Mutation::DockStruts {
+                        window: wid,
+                        struts: Some(struts),
+                    });
                 }
+                return Ok(true);
             }
-            now.scratchpad
-                .windows
-                .retain(|(_, wid)| wid != event.window_id.as_str());
-            if let Some(focused) = &now.focused_window {
-                if focused == event.window_id.as_str() {
-                    now.focused_window = None
-                }
+            if let Some(name) = pending_scratchpad(now, wid, backend, config) {
+                now.apply(Mutation::Pending {
+                    name: name.clone(),
+                    pending: false,
+                });
+                now.apply(Mutation::AddScratchpad {
+                    name: name.clone(),
+                    window: wid,
+                });
+                toggle_scratchpad(now, name, wid);
+                return Ok(true);
+            }
+            let ws = now.focused_workspace;
+            add_window_to_workspace(now, ws, wid);
+            // Hints set before mapping never arrive as changes, and a window
+            // that gets focus right away needs no attention
+            if now.focus_target() != Some(wid) && backend.is_urgent(wid) {
+                now.apply(Mutation::Urgent {
+                    window: wid,
+                    urgent: true,
+                });
             }
         }
+        WindowEventType::DestroyNotify => forget_window(now, wid),
         WindowEventType::FocusIn => {
-            if now.workspaces[now.focused_workspace]
-                .focus_history
-                .contains(&event.window_id)
-            {
-                focus_window_on_workspace(
-                    &mut now.workspaces[now.focused_workspace],
-                    event.window_id,
-                );
+            if now.urgent.contains(&wid) {
+                now.apply(Mutation::Urgent {
+                    window: wid,
+                    urgent: false,
+                });
+            }
+            let ws = now.focused_workspace;
+            if now.workspaces[ws].focus_history.contains(&wid) {
+                focus_window_on_workspace(now, ws, wid);
+            }
+            // X focus is the truth, also for sticky windows and scratchpads that are
+            // in no focus history, or the commands after it would act on another window
+            if now.focused_window != Some(wid) {
+                now.apply(Mutation::FocusWindow(Some(wid)));
+            }
+        }
+        WindowEventType::FocusOut => return Ok(false),
+    }
+    Ok(true)
+}
+
+pub fn handle_window_command(
+    now: &mut Transaction,
+    command: WindowCommand,
+    backend: &mut dyn Backend,
+    config: &Config,
+) -> Result<bool, HandlerError> {
+    match command {
+        WindowCommand::Close => {
+            if let Some(wid) = now.focused_window {
+                backend.close_window(wid, config);
+            }
+            Ok(false)
+        }
+        WindowCommand::FloatToggle => {
+            let ws = now.focused_workspace;
+            let workspace = &now.workspaces[ws];
+            let wid = match now.focused_window {
+                Some(wid) if workspace.windows.contains(&wid) => wid,
+                _ => return Ok(false),
+            };
+            let rect = if workspace.floating_rect(wid).is_some() {
+                None
             } else {
-                now.focused_window = Some(event.window_id);
+                Some(now.usable_area(config).centered(config.scratchpad_size))
+            };
+            now.set_floating(ws, wid, rect);
+            Ok(true)
+        }
+        WindowCommand::StickyToggle => {
+            let wid = match now.focused_window {
+                Some(wid) => wid,
+                None => return Ok(false),
+            };
+            if now.sticky_rect(wid).is_some() {
+                now.apply(Mutation::Sticky {
+                    window: wid,
+                    rect: None,
+                });
+                let ws = now.focused_workspace;
+                add_window_to_workspace(now, ws, wid);
+                return Ok(true);
             }
+            // Scratchpads are already shown everywhere
+            let ws = match workspace_of_window(now, wid) {
+                Some(ws) => ws,
+                None => return Ok(false),
+            };
+            let rect = now.workspaces[ws]
+                .floating_rect(wid)
+                .unwrap_or_else(|| now.usable_area(config).centered(config.scratchpad_size));
+            remove_window_from_workspace(now, ws, wid);
+            now.apply(Mutation::Sticky {
+                window: wid,
+                rect: Some(rect),
+            });
+            Ok(true)
         }
-        WindowEventType::FocusOut => return false,
     }
-    true
 }
 
-fn remove_window_from_workspace(state: &mut WorkspaceState, window_id: impl Into<String> + Clone) {
-    let window_id = window_id.into();
-    state.windows.retain(|wid| wid != window_id.as_str());
-    state.focus_history.retain(|wid| wid != window_id.as_str());
-    if let Some(fullscreen) = &state.fullscreen {
-        if fullscreen == window_id.as_str() {
-            state.fullscreen = None;
+pub fn handle_pointer_event(
+    now: &mut Transaction,
+    event: PointerEvent,
+    config: &Config,
+) -> Result<bool, HandlerError> {
+    match event {
+        PointerEvent::Press {
+            window_id,
+            button,
+            x,
+            y,
+        } => {
+            let kind = match button {
+                1 => DragKind::Move,
+                3 => DragKind::Resize,
+                _ => return Ok(false),
+            };
+            let ws = now.focused_workspace;
+            let workspace = &now.workspaces[ws];
+            let sticky = now.sticky_rect(window_id);
+            if workspace.fullscreen.is_some()
+                || !(workspace.windows.contains(&window_id) || sticky.is_some())
+            {
+                return Ok(false);
+            }
+            let rect = match workspace.floating_rect(window_id).or(sticky) {
+                Some(rect) => rect,
+                None => match now
+                    .tiled_rects(config)
+                    .into_iter()
+                    .find(|(wid, _)| *wid == window_id)
+                {
+                    Some((_, rect)) => rect,
+                    None => return Ok(false),
+                },
+            };
+            if sticky.is_none() {
+                focus_window_on_workspace(now, ws, window_id);
+            }
+            if now.focused_window != Some(window_id) {
+                now.apply(Mutation::FocusWindow(Some(window_id)));
+            }
+            now.apply(Mutation::Drag(Some(Drag {
+                window_id,
+                kind,
+                origin: (x, y),
+                rect,
+            })));
+        }
+        PointerEvent::Motion { x, y } => {
+            let drag = match &now.drag {
+                Some(drag) => drag.clone(),
+                None => return Ok(false),
+            };
+            let rect = drag.apply((x, y));
+            if now.sticky_rect(drag.window_id).is_some() {
+                now.apply(Mutation::Sticky {
+                    window: drag.window_id,
+                    rect: Some(rect),
+                });
+                return Ok(true);
+            }
+            let ws = now.focused_workspace;
+            let floating = now.workspaces[ws].floating_rect(drag.window_id).is_some();
+            // Resizing a tiled window floats it, tiled windows are dropped on release
+            if !floating && drag.kind != DragKind::Resize {
+                return Ok(false);
+            }
+            now.set_floating(ws, drag.window_id, Some(rect));
+        }
+        PointerEvent::Release { x, y } => {
+            let drag = match &now.drag {
+                Some(drag) => drag.clone(),
+                None => return Ok(false),
+            };
+            now.apply(Mutation::Drag(None));
+            let ws = now.focused_workspace;
+            if drag.kind == DragKind::Move
+                && now.workspaces[ws].floating_rect(drag.window_id).is_none()
+                && now.sticky_rect(drag.window_id).is_none()
+            {
+                // Dropping a tiled window swaps it with the one under the pointer,
+                // or floats it if there is none
+                let target = now
+                    .tiled_rects(config)
+                    .into_iter()
+                    .find(|(_, rect)| rect.contains((x, y)))
+                    .map(|(wid, _)| wid);
+                match target {
+                    Some(target) if target != drag.window_id => {
+                        swap_windows(now, ws, drag.window_id, target)
+                    }
+                    Some(_) => {}
+                    None => now.set_floating(ws, drag.window_id, Some(drag.apply((x, y)))),
+                }
+            }
         }
     }
+    Ok(true)
 }
 
-fn add_window_to_workspace(state: &mut WorkspaceState, window_id: impl Into<String> + Clone) {
-    state.windows.push(window_id.clone().into());
-    state.focus_history.push(window_id.into());
+/// Ends a drag whose window left the focused workspace or whose workspace
+/// was switched away from, so the next motion or release has nothing to move.
+/// Runs after every handled event.
+pub fn end_stale_drag(now: &mut Transaction) {
+    let window_id = match &now.drag {
+        Some(drag) => drag.window_id,
+        None => return,
+    };
+    let ws = now.focused_workspace;
+    let shown =
+        now.workspaces[ws].windows.contains(&window_id) || now.sticky_rect(window_id).is_some();
+    if !shown || ws != now.before().focused_workspace {
+        now.apply(Mutation::Drag(None));
+    }
 }
 
-fn focus_window_on_workspace(state: &mut WorkspaceState, window_id: impl Into<String>) {
-    let window_id = window_id.into();
-    state.focus_history.retain(|wid| wid != window_id.as_str());
-    state.focus_history.push(window_id);
+pub fn handle_query_event(now: &WMState, event: QueryEvent) -> String {
+    match event {
+        QueryEvent::State => {
+            let mut lines = vec![
+                format!("focused_workspace {}", now.focused_workspace + 1),
+                format!(
+                    "focused_window {}",
+                    now.focused_window
+                        .map_or("none".to_string(), |wid| wid.to_string())
+                ),
+            ];
+            for (i, workspace) in now.workspaces.iter().enumerate() {
+                lines.push(format!(
+                    "workspace {} {}",
+                    i + 1,
+                    join_windows(&workspace.windows)
+                ));
+            }
+            for (name, wid) in &now.scratchpad.windows {
+                lines.push(format!("scratchpad {} {}", name, wid));
+            }
+            lines.push(format!("shown {}", now.scratchpad.shown.join(" ")));
+            let sticky: Vec<WindowId> = now.sticky.iter().map(|(wid, _)| *wid).collect();
+            lines.push(format!("sticky {}", join_windows(&sticky)));
+            lines.push(format!("urgent {}", join_windows(&now.urgent)));
+            lines
+                .iter()
+                .map(|line| line.trim_end())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        QueryEvent::Scratchpads => {
+            let mut lines: Vec<String> = now
+                .scratchpad
+                .windows
+                .iter()
+                .map(|(name, wid)| {
+                    let visibility = if now.scratchpad.shown.contains(name) {
+                        "shown"
+                    } else {
+                        "hidden"
+                    };
+                    format!("{} {} {}", name, wid, visibility)
+                })
+                .collect();
+            lines.extend(
+                now.scratchpad
+                    .pending
+                    .iter()
+                    .map(|name| format!("{} none pending", name)),
+            );
+            lines.join("\n")
+        }
+    }
+}
+
+fn join_windows(windows: &[WindowId]) -> String {
+    let windows: Vec<String> = windows.iter().map(WindowId::to_string).collect();
+    windows.join(" ")
+}
+
+/// Switches to the window's workspace and focuses it, hidden scratchpads get shown.
+fn activate_window(now: &mut Transaction, wid: WindowId) -> bool {
+    if let Some(ws) = workspace_of_window(now, wid) {
+        switch_workspace(now, ws);
+        focus_window_on_workspace(now, ws, wid);
+        return true;
+    }
+    if let Some(name) = now.scratchpad.name(wid).cloned() {
+        if now.scratchpad.shown.last() != Some(&name) {
+            toggle_scratchpad(now, name, wid);
+            return true;
+        }
+    }
+    false
+}
+
+/// Hides the scratchpad if it's the top one, shows or raises it otherwise.
+fn toggle_scratchpad(now: &mut Transaction, name: String, wid: WindowId) {
+    if now.scratchpad.shown.last() == Some(&name) {
+        // Reset fullscreen if it's the hidden scratchpad window
+        let ws = now.focused_workspace;
+        if now.workspaces[ws].fullscreen == Some(wid) {
+            now.set_fullscreen(ws, None);
+        }
+        now.apply(Mutation::HideScratchpad(name));
+    } else {
+        // Show it or raise it above the other shown scratchpads
+        now.apply(Mutation::ShowScratchpad(name));
+    }
+}
+
+/// Index of the workspace's focused window in `windows`.
+fn focused_index(workspace: &WorkspaceState, windows: &[WindowId]) -> Option<usize> {
+    let focused_wid = workspace.focus_history.last()?;
+    windows.iter().position(|wid| wid == focused_wid)
+}
+
+fn swap_windows(now: &mut Transaction, ws: usize, a: WindowId, b: WindowId) {
+    let mut windows = now.workspaces[ws].windows.clone();
+    let a = windows.iter().position(|wid| *wid == a);
+    let b = windows.iter().position(|wid| *wid == b);
+    if let (Some(a), Some(b)) = (a, b) {
+        windows.swap(a, b);
+        now.apply(Mutation::OrderWindows {
+            workspace: ws,
+            windows,
+        });
+    }
+}
+
+/// Index of the window next to `index` in the two column layout of `n` windows.
+fn neighbour_index(n: usize, index: usize, direction: Direction) -> Option<usize> {
+    if n < 2 {
+        return None;
+    }
+    let left_n = n / 2;
+    let right_n = n - left_n;
+    match direction {
+        Direction::Left if index >= left_n => Some(index.saturating_sub(right_n)),
+        Direction::Right if index < left_n => Some(index + left_n),
+        Direction::Up if index != 0 && index != left_n => Some(index - 1),
+        Direction::Down if index != (left_n - 1) && index != (n - 1) => Some(index + 1),
+        _ => None,
+    }
+}
+
+/// Launched scratchpad that `window_id` belongs to, by its `WM_CLASS`.
+fn pending_scratchpad(
+    now: &WMState,
+    window_id: WindowId,
+    backend: &dyn Backend,
+    config: &Config,
+) -> Option<String> {
+    if now.scratchpad.pending.is_empty() {
+        return None;
+    }
+    let (instance, class) = backend.window_class(window_id)?;
+    now.scratchpad
+        .pending
+        .iter()
+        .find(|name| {
+            config
+                .scratchpad(name)
+                .is_some_and(|scratchpad| scratchpad.class == instance || scratchpad.class == class)
+        })
+        .cloned()
+}
+
+fn check_workspace(now: &WMState, ws: usize) -> Result<(), HandlerError> {
+    if ws >= now.workspaces.len() {
+        return Err(HandlerError::NoSuchWorkspace(ws));
+    }
+    Ok(())
+}
+
+/// Scratchpads follow the focused workspace, but drop fullscreen on the one left behind.
+fn switch_workspace(now: &mut Transaction, ws: usize) {
+    let from = now.focused_workspace;
+    if from == ws {
+        return;
+    }
+    let scratchpad = &now.scratchpad;
+    if now.workspaces[from]
+        .fullscreen
+        .is_some_and(|wid| scratchpad.name(wid).is_some())
+    {
+        now.set_fullscreen(from, None);
+    }
+    now.apply(Mutation::FocusWorkspace { from, to: ws });
+}
+
+fn workspace_of_window(now: &WMState, window_id: WindowId) -> Option<usize> {
+    now.workspaces
+        .iter()
+        .position(|workspace| workspace.windows.contains(&window_id))
+}
+
+fn move_window_to_workspace(now: &mut Transaction, window_id: WindowId, ws: usize) -> bool {
+    match workspace_of_window(now, window_id) {
+        Some(from) if from != ws => {
+            remove_window_from_workspace(now, from, window_id);
+            add_window_to_workspace(now, ws, window_id);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn remove_window_from_workspace(now: &mut Transaction, ws: usize, window_id: WindowId) {
+    if now.workspaces[ws].fullscreen == Some(window_id) {
+        now.set_fullscreen(ws, None);
+    }
+    now.apply(Mutation::RemoveWindow {
+        workspace: ws,
+        window: window_id,
+    });
+}
+
+fn add_window_to_workspace(now: &mut Transaction, ws: usize, window_id: WindowId) {
+    now.apply(Mutation::AddWindow {
+        workspace: ws,
+        window: window_id,
+    });
+}
+
+fn focus_window_on_workspace(now: &mut Transaction, ws: usize, window_id: WindowId) {
+    if now.workspaces[ws].focus_history.last() != Some(&window_id) {
+        now.apply(Mutation::FocusOnWorkspace {
+            workspace: ws,
+            window: window_id,
+        });
+    }
+}
+
+/// Drops every trace of a destroyed window.
+fn forget_window(now: &mut Transaction, window_id: WindowId) {
+    if now.created.contains(&window_id) {
+        now.apply(Mutation::Created {
+            window: window_id,
+            created: false,
+        });
+    }
+    // Fullscreen scratchpads are on a workspace without being one of its windows
+    for ws in 0..now.workspaces.len() {
+        if now.workspaces[ws].fullscreen == Some(window_id) {
+            now.set_fullscreen(ws, None);
+        }
+    }
+    if let Some(ws) = workspace_of_window(now, window_id) {
+        remove_window_from_workspace(now, ws, window_id);
+    }
+    if let Some(name) = now.scratchpad.name(window_id).cloned() {
+        now.apply(Mutation::RemoveScratchpad {
+            name,
+            window: window_id,
+        });
+    }
+    if now.dock_struts.iter().any(|(wid, _)| *wid == window_id) {
+        now.apply(Mutation::DockStruts {
+            window: window_id,
+            struts: None,
+        });
+    }
+    if now.sticky_rect(window_id).is_some() {
+        now.apply(Mutation::Sticky {
+            window: window_id,
+            rect: None,
+        });
+    }
+    if now.urgent.contains(&window_id) {
+        now.apply(Mutation::Urgent {
+            window: window_id,
+            urgent: false,
+        });
+    }
+    if now.focused_window == Some(window_id) {
+        now.apply(Mutation::FocusWindow(None));
+    }
+    if now
+        .drag
+        .as_ref()
+        .is_some_and(|drag| drag.window_id == window_id)
+    {
+        now.apply(Mutation::Drag(None));
+    }
 }
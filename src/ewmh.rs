@@ -0,0 +1,703 @@
+use crate::geometry::Struts;
+use crate::{
+    ClientEvent, FocusMode, PointerEvent, StateAction, WindowEvent, WindowEventType, WindowId,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Allow, AtomEnum, ButtonIndex, ChangeWindowAttributesAux, ClientMessageEvent,
+    ConnectionExt as _, CreateWindowAux, EventMask, GrabMode, ModMask, MotionNotifyEvent,
+    NotifyDetail, NotifyMode, PropMode, Window, WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::CURRENT_TIME;
+
+x11rb::atom_manager! {
+    pub Atoms: AtomsCookie {
+        UTF8_STRING,
+        _NET_SUPPORTED,
+        _NET_SUPPORTING_WM_CHECK,
+        _NET_WM_NAME,
+        _NET_NUMBER_OF_DESKTOPS,
+        _NET_CURRENT_DESKTOP,
+        _NET_DESKTOP_NAMES,
+        _NET_CLIENT_LIST,
+        _NET_ACTIVE_WINDOW,
+        _NET_WM_DESKTOP,
+        _NET_CLOSE_WINDOW,
+        _NET_WM_STATE,
+        _NET_WM_STATE_FULLSCREEN,
+        _NET_WM_STATE_DEMANDS_ATTENTION,
+        _NET_WM_STRUT,
+        _NET_WM_STRUT_PARTIAL,
+        _NET_WM_PING,
+        WM_PROTOCOLS,
+        WM_DELETE_WINDOW,
+    }
+}
+
+/// `_NET_WM_DESKTOP` value for windows shown on every desktop.
+pub const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+
+/// Publishes EWMH root and window properties for panels and pagers and
+/// listens for their requests, for super+drag and for pointer focus.
+/// wmutils can't write typed list properties or receive client messages,
+/// so this talks to X directly.
+pub struct Ewmh {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+    focus_mode: FocusMode,
+    closing: Mutex<Vec<Closing>>,
+    /// Woken when a window is asked to close, see `kill_hung_clients`
+    closing_changed: Condvar,
+    /// Event read past the last of a run of `MotionNotify`s
+    queued: Mutex<Option<Event>>,
+    /// Set between `begin_layout` and `end_layout`
+    laying_out: AtomicBool,
+    /// Sequence number of the request that ended the last layout
+    layout_serial: Mutex<Option<u16>>,
+}
+
+/// Window that was asked to close and hasn't been destroyed yet.
+struct Closing {
+    window: Window,
+    /// Answered the `_NET_WM_PING` sent with the last request
+    responsive: bool,
+    /// When its client gets killed unless it answered by then
+    deadline: Instant,
+}
+
+/// Focusing by pointer goes through the same path as X focus changes.
+fn focus_in(window: Window) -> crate::Event {
+    crate::Event::Window(WindowEvent {
+        window_id: WindowId(window),
+        event_type: WindowEventType::FocusIn,
+    })
+}
+
+impl Ewmh {
+    /// Connects to `$DISPLAY` and announces EWMH support, `None` without X.
+    pub fn connect(focus_mode: FocusMode) -> Option<Ewmh> {
+        let (conn, screen) = RustConnection::connect(None).ok()?;
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&conn).ok()?.reply().ok()?;
+        let ewmh = Ewmh {
+            conn,
+            root,
+            atoms,
+            focus_mode,
+            closing: Mutex::new(Vec::new()),
+            closing_changed: Condvar::new(),
+            queued: Mutex::new(None),
+            laying_out: AtomicBool::new(false),
+            layout_serial: Mutex::new(None),
+        };
+        ewmh.announce().ok()?;
+        Some(ewmh)
+    }
+
+    fn announce(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let atoms = &self.atoms;
+        let check = self.conn.generate_id()?;
+        self.conn.create_window(
+            0,
+            check,
+            self.root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            0,
+            &CreateWindowAux::new(),
+        )?;
+        for window in &[self.root, check] {
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                *window,
+                atoms._NET_SUPPORTING_WM_CHECK,
+                AtomEnum::WINDOW,
+                &[check],
+            )?;
+        }
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            check,
+            atoms._NET_WM_NAME,
+            atoms.UTF8_STRING,
+            b"derpy-wm",
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            atoms._NET_SUPPORTED,
+            AtomEnum::ATOM,
+            &[
+                atoms._NET_SUPPORTING_WM_CHECK,
+                atoms._NET_NUMBER_OF_DESKTOPS,
+                atoms._NET_CURRENT_DESKTOP,
+                atoms._NET_DESKTOP_NAMES,
+                atoms._NET_CLIENT_LIST,
+                atoms._NET_ACTIVE_WINDOW,
+                atoms._NET_WM_DESKTOP,
+                atoms._NET_CLOSE_WINDOW,
+                atoms._NET_WM_STATE,
+                atoms._NET_WM_STATE_FULLSCREEN,
+                atoms._NET_WM_STATE_DEMANDS_ATTENTION,
+                atoms._NET_WM_STRUT,
+                atoms._NET_WM_STRUT_PARTIAL,
+                atoms._NET_WM_PING,
+            ],
+        )?;
+        // Pagers send their requests to the root window
+        self.conn.change_window_attributes(
+            self.root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::SUBSTRUCTURE_NOTIFY),
+        )?;
+        // super+drag moves and super+right-drag resizes, whatever the lock keys are
+        let drag_mask =
+            EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION;
+        for button in &[ButtonIndex::M1, ButtonIndex::M3] {
+            for locks in &[
+                ModMask::from(0u16),
+                ModMask::LOCK,
+                ModMask::M2,
+                ModMask::LOCK | ModMask::M2,
+            ] {
+                self.conn.grab_button(
+                    false,
+                    self.root,
+                    drag_mask,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                    x11rb::NONE,
+                    x11rb::NONE,
+                    *button,
+                    ModMask::M4 | *locks,
+                )?;
+            }
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn set_cardinals(&self, window: Window, property: u32, values: &[u32]) {
+        self.conn
+            .change_property32(
+                PropMode::REPLACE,
+                window,
+                property,
+                AtomEnum::CARDINAL,
+                values,
+            )
+            .ok();
+        self.conn.flush().ok();
+    }
+
+    fn set_windows(&self, property: u32, windows: &[Window]) {
+        self.conn
+            .change_property32(
+                PropMode::REPLACE,
+                self.root,
+                property,
+                AtomEnum::WINDOW,
+                windows,
+            )
+            .ok();
+        self.conn.flush().ok();
+    }
+
+    pub fn set_number_of_desktops(&self, count: usize) {
+        self.set_cardinals(
+            self.root,
+            self.atoms._NET_NUMBER_OF_DESKTOPS,
+            &[count as u32],
+        );
+    }
+
+    pub fn set_current_desktop(&self, desktop: usize) {
+        self.set_cardinals(
+            self.root,
+            self.atoms._NET_CURRENT_DESKTOP,
+            &[desktop as u32],
+        );
+    }
+
+    pub fn set_desktop_names(&self, names: &[String]) {
+        let mut data = Vec::new();
+        for name in names {
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+        }
+        self.conn
+            .change_property8(
+                PropMode::REPLACE,
+                self.root,
+                self.atoms._NET_DESKTOP_NAMES,
+                self.atoms.UTF8_STRING,
+                &data,
+            )
+            .ok();
+        self.conn.flush().ok();
+    }
+
+    pub fn set_client_list(&self, windows: impl IntoIterator<Item = WindowId>) {
+        let windows: Vec<Window> = windows.into_iter().map(|wid| wid.0).collect();
+        self.set_windows(self.atoms._NET_CLIENT_LIST, &windows);
+    }
+
+    pub fn set_active_window(&self, window_id: Option<WindowId>) {
+        let window = window_id.map_or(0, |wid| wid.0);
+        self.set_windows(self.atoms._NET_ACTIVE_WINDOW, &[window]);
+    }
+
+    pub fn set_window_desktop(&self, window_id: WindowId, desktop: u32) {
+        self.set_cardinals(window_id.0, self.atoms._NET_WM_DESKTOP, &[desktop]);
+    }
+
+    /// Adds or removes a single atom of the window's `_NET_WM_STATE`,
+    /// keeping the states set by the client itself.
+    fn change_window_state(&self, window_id: WindowId, state: u32, enabled: bool) {
+        let window = window_id.0;
+        let mut states: Vec<u32> = self
+            .conn
+            .get_property(
+                false,
+                window,
+                self.atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| reply.value32().map(Iterator::collect))
+            .unwrap_or_default();
+        states.retain(|atom| *atom != state);
+        if enabled {
+            states.push(state);
+        }
+        self.conn
+            .change_property32(
+                PropMode::REPLACE,
+                window,
+                self.atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                &states,
+            )
+            .ok();
+        self.conn.flush().ok();
+    }
+
+    pub fn set_fullscreen(&self, window_id: WindowId, fullscreen: bool) {
+        self.change_window_state(window_id, self.atoms._NET_WM_STATE_FULLSCREEN, fullscreen);
+    }
+
+    pub fn set_demands_attention(&self, window_id: WindowId, urgent: bool) {
+        self.change_window_state(
+            window_id,
+            self.atoms._NET_WM_STATE_DEMANDS_ATTENTION,
+            urgent,
+        );
+    }
+
+    /// Subscribes to property changes of a managed window to track its urgency hint,
+    /// and to clicks or pointer crossings depending on the focus mode.
+    pub fn watch_window(&self, window_id: WindowId) {
+        let window = window_id.0;
+        let event_mask = match self.focus_mode {
+            FocusMode::Click => EventMask::PROPERTY_CHANGE,
+            FocusMode::FollowsMouse => EventMask::PROPERTY_CHANGE | EventMask::ENTER_WINDOW,
+        };
+        self.conn
+            .change_window_attributes(
+                window,
+                &ChangeWindowAttributesAux::new().event_mask(event_mask),
+            )
+            .ok();
+        if self.focus_mode == FocusMode::Click {
+            // Clicks are frozen until they are replayed to the window after focusing it
+            self.conn
+                .grab_button(
+                    true,
+                    window,
+                    EventMask::BUTTON_PRESS,
+                    GrabMode::SYNC,
+                    GrabMode::ASYNC,
+                    x11rb::NONE,
+                    x11rb::NONE,
+                    ButtonIndex::ANY,
+                    ModMask::ANY,
+                )
+                .ok();
+        }
+        self.conn.flush().ok();
+    }
+
+    /// Subscribes to property changes of a dock to follow its struts.
+    pub fn watch_dock(&self, window_id: WindowId) {
+        self.conn
+            .change_window_attributes(
+                window_id.0,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )
+            .ok();
+        self.conn.flush().ok();
+    }
+
+    /// Reads `_NET_WM_STRUT_PARTIAL`, falling back to the older `_NET_WM_STRUT`.
+    /// Only the first four values matter, the partial one adds their extents.
+    pub fn window_struts(&self, window_id: WindowId) -> Option<Struts> {
+        [self.atoms._NET_WM_STRUT_PARTIAL, self.atoms._NET_WM_STRUT]
+            .iter()
+            .find_map(|property| {
+                let reply = self
+                    .conn
+                    .get_property(false, window_id.0, *property, AtomEnum::CARDINAL, 0, 4)
+                    .ok()?
+                    .reply()
+                    .ok()?;
+                let values: Vec<usize> = reply.value32()?.map(|v| v as usize).collect();
+                match values.as_slice() {
+                    [left, right, top, bottom] => Some(Struts {
+                        left: *left,
+                        right: *right,
+                        top: *top,
+                        bottom: *bottom,
+                    }),
+                    _ => None,
+                }
+            })
+    }
+
+    /// Reads `WM_CLASS`, the instance name and the class name each ended by a NUL.
+    pub fn window_class(&self, window_id: WindowId) -> Option<(String, String)> {
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                window_id.0,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                0,
+                1024,
+            )
+            .ok()?
+            .reply()
+            .ok()?;
+        let mut names = reply
+            .value
+            .split(|byte| *byte == 0)
+            .map(|name| String::from_utf8_lossy(name).into_owned());
+        Some((names.next()?, names.next()?))
+    }
+
+    /// Ignores the pointer entering windows until `end_layout`, windows moved or
+    /// mapped under it weren't picked by the user.
+    pub fn begin_layout(&self) {
+        self.laying_out.store(true, Ordering::SeqCst);
+    }
+
+    /// Waits for the server to catch up with the layout, the crossings it caused
+    /// are ignored by their sequence number once they are read.
+    pub fn end_layout(&self) {
+        if let Ok(cookie) = self.conn.get_input_focus() {
+            let serial = cookie.sequence_number() as u16;
+            cookie.reply().ok();
+            *self.layout_serial.lock().unwrap() = Some(serial);
+        }
+        self.laying_out.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether a crossing with this sequence number came from laying windows out.
+    fn caused_by_layout(&self, sequence: u16) -> bool {
+        if self.laying_out.load(Ordering::SeqCst) {
+            return true;
+        }
+        let mut serial = self.layout_serial.lock().unwrap();
+        match *serial {
+            Some(serial) if (sequence.wrapping_sub(serial) as i16) < 0 => true,
+            // Events come in order, everything after this one is newer too
+            _ => {
+                *serial = None;
+                false
+            }
+        }
+    }
+
+    /// Asks the window to close with `WM_DELETE_WINDOW` so it can save its work,
+    /// clients that don't support it are killed. `kill_hung_clients` kills the client
+    /// once `timeout` passed without it answering the `_NET_WM_PING` sent along, and
+    /// so does asking again before that. A client asking whether to save changes
+    /// answers the ping and is left alone.
+    pub fn close_window(&self, window_id: WindowId, timeout: Duration) {
+        let window = window_id.0;
+        let protocols = self.protocols(window);
+        let mut closing = self.closing.lock().unwrap();
+        let asked = closing.iter().position(|closing| closing.window == window);
+        let hung = asked.is_some_and(|i| !closing[i].responsive);
+        if !protocols.contains(&self.atoms.WM_DELETE_WINDOW) || hung {
+            closing.retain(|closing| closing.window != window);
+            self.kill_client(window);
+            return;
+        }
+        self.send_protocol(window, self.atoms.WM_DELETE_WINDOW);
+        // Clients without `_NET_WM_PING` never answer and are killed after the timeout
+        if protocols.contains(&self.atoms._NET_WM_PING) {
+            self.send_protocol(window, self.atoms._NET_WM_PING);
+        }
+        let deadline = Instant::now() + timeout;
+        match asked {
+            Some(i) => {
+                closing[i].responsive = false;
+                closing[i].deadline = deadline;
+            }
+            None => closing.push(Closing {
+                window,
+                responsive: false,
+                deadline,
+            }),
+        }
+        self.closing_changed.notify_one();
+    }
+
+    /// Kills the clients of windows asked to close that are still around and
+    /// didn't answer their ping in time. Blocks forever, run it on its own thread.
+    pub fn kill_hung_clients(&self) {
+        let mut closing = self.closing.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let (hung, waiting): (Vec<&Closing>, Vec<&Closing>) = closing
+                .iter()
+                .filter(|closing| !closing.responsive)
+                .partition(|closing| closing.deadline <= now);
+            let hung: Vec<Window> = hung.iter().map(|closing| closing.window).collect();
+            let next = waiting.iter().map(|closing| closing.deadline).min();
+            // Destroyed windows were dropped by their `DestroyNotify`
+            for window in &hung {
+                self.kill_client(*window);
+            }
+            closing.retain(|closing| !hung.contains(&closing.window));
+            closing = match next {
+                Some(next) => {
+                    let wait = next.saturating_duration_since(Instant::now());
+                    self.closing_changed.wait_timeout(closing, wait).unwrap().0
+                }
+                None => self.closing_changed.wait(closing).unwrap(),
+            };
+        }
+    }
+
+    fn kill_client(&self, window: Window) {
+        self.conn.kill_client(window).ok();
+        self.conn.flush().ok();
+    }
+
+    /// Protocols the window lists in `WM_PROTOCOLS`.
+    fn protocols(&self, window: Window) -> Vec<u32> {
+        self.conn
+            .get_property(
+                false,
+                window,
+                self.atoms.WM_PROTOCOLS,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| reply.value32().map(Iterator::collect))
+            .unwrap_or_default()
+    }
+
+    fn send_protocol(&self, window: Window, protocol: u32) {
+        let message = ClientMessageEvent::new(
+            32,
+            window,
+            self.atoms.WM_PROTOCOLS,
+            [protocol, CURRENT_TIME, window, 0, 0],
+        );
+        self.conn
+            .send_event(false, window, EventMask::NO_EVENT, message)
+            .ok();
+        self.conn.flush().ok();
+    }
+
+    /// Whether `WM_HINTS` has the urgency flag set.
+    /// Whether the urgency hint or `_NET_WM_STATE_DEMANDS_ATTENTION` is set.
+    pub fn is_urgent(&self, window_id: WindowId) -> bool {
+        let demands_attention = self
+            .conn
+            .get_property(
+                false,
+                window_id.0,
+                self.atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| {
+                let mut states = reply.value32()?;
+                Some(states.any(|state| state == self.atoms._NET_WM_STATE_DEMANDS_ATTENTION))
+            })
+            .unwrap_or(false);
+        demands_attention || self.has_urgency_hint(window_id.0)
+    }
+
+    fn has_urgency_hint(&self, window: Window) -> bool {
+        const URGENCY_HINT: u32 = 1 << 8;
+        self.conn
+            .get_property(false, window, AtomEnum::WM_HINTS, AtomEnum::WM_HINTS, 0, 1)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| reply.value32().and_then(|mut flags| flags.next()))
+            .map(|flags| flags & URGENCY_HINT != 0)
+            .unwrap_or(false)
+    }
+
+    /// Blocks until a pager or client sends a supported request, a watched
+    /// window changes its urgency, a dock its struts or the pointer drags a window,
+    /// `None` once the X connection is gone.
+    pub fn next_event(&self) -> Option<crate::Event> {
+        loop {
+            let queued = self.queued.lock().unwrap().take();
+            let event = match queued {
+                Some(event) => event,
+                None => self.conn.wait_for_event().ok()?,
+            };
+            match event {
+                // Clients answer pings by sending them back to the root window
+                Event::ClientMessage(message)
+                    if message.type_ == self.atoms.WM_PROTOCOLS
+                        && message.data.as_data32()[0] == self.atoms._NET_WM_PING =>
+                {
+                    let window = message.data.as_data32()[2];
+                    let mut closing = self.closing.lock().unwrap();
+                    if let Some(closing) = closing.iter_mut().find(|c| c.window == window) {
+                        closing.responsive = true;
+                    }
+                }
+                Event::DestroyNotify(notify) => {
+                    let mut closing = self.closing.lock().unwrap();
+                    closing.retain(|closing| closing.window != notify.window);
+                }
+                Event::ClientMessage(message) => {
+                    if let Some(event) = self.client_event(&message) {
+                        return Some(crate::Event::Client(event));
+                    }
+                }
+                Event::PropertyNotify(notify) if notify.atom == u32::from(AtomEnum::WM_HINTS) => {
+                    let action = if self.has_urgency_hint(notify.window) {
+                        StateAction::Add
+                    } else {
+                        StateAction::Remove
+                    };
+                    return Some(crate::Event::Client(ClientEvent::Attention(
+                        WindowId(notify.window),
+                        action,
+                    )));
+                }
+                Event::PropertyNotify(notify)
+                    if notify.atom == self.atoms._NET_WM_STRUT
+                        || notify.atom == self.atoms._NET_WM_STRUT_PARTIAL =>
+                {
+                    return Some(crate::Event::Client(ClientEvent::Struts(
+                        WindowId(notify.window),
+                        self.window_struts(WindowId(notify.window)),
+                    )));
+                }
+                // Click to focus, grabbed in `watch_window`
+                Event::ButtonPress(press) if press.event != self.root => {
+                    self.conn
+                        .allow_events(Allow::REPLAY_POINTER, x11rb::CURRENT_TIME)
+                        .ok();
+                    self.conn.flush().ok();
+                    return Some(focus_in(press.event));
+                }
+                Event::EnterNotify(enter)
+                    if enter.mode == NotifyMode::NORMAL
+                        && enter.detail != NotifyDetail::INFERIOR
+                        && !self.caused_by_layout(enter.sequence) =>
+                {
+                    return Some(focus_in(enter.event));
+                }
+                // Presses on the bare root window have no child
+                Event::ButtonPress(press) if press.child != x11rb::NONE => {
+                    return Some(crate::Event::Pointer(PointerEvent::Press {
+                        window_id: WindowId(press.child),
+                        button: press.detail,
+                        x: press.root_x.into(),
+                        y: press.root_y.into(),
+                    }));
+                }
+                Event::MotionNotify(motion) => {
+                    let motion = self.last_motion(motion);
+                    return Some(crate::Event::Pointer(PointerEvent::Motion {
+                        x: motion.root_x.into(),
+                        y: motion.root_y.into(),
+                    }));
+                }
+                Event::ButtonRelease(release) => {
+                    return Some(crate::Event::Pointer(PointerEvent::Release {
+                        x: release.root_x.into(),
+                        y: release.root_y.into(),
+                    }));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Skips to the last of the already queued `MotionNotify`s, a drag only
+    /// needs the latest pointer position.
+    fn last_motion(&self, mut motion: MotionNotifyEvent) -> MotionNotifyEvent {
+        loop {
+            match self.conn.poll_for_event() {
+                Ok(Some(Event::MotionNotify(next))) => motion = next,
+                Ok(Some(event)) => {
+                    *self.queued.lock().unwrap() = Some(event);
+                    return motion;
+                }
+                _ => return motion,
+            }
+        }
+    }
+
+    fn client_event(&self, message: &ClientMessageEvent) -> Option<ClientEvent> {
+        let atoms = &self.atoms;
+        let data = message.data.as_data32();
+        let window_id = WindowId(message.window);
+        if message.type_ == atoms._NET_CURRENT_DESKTOP {
+            Some(ClientEvent::CurrentDesktop(data[0] as usize))
+        } else if message.type_ == atoms._NET_ACTIVE_WINDOW {
+            Some(ClientEvent::ActiveWindow(window_id))
+        } else if message.type_ == atoms._NET_WM_DESKTOP {
+            Some(ClientEvent::WindowDesktop(window_id, data[0] as usize))
+        } else if message.type_ == atoms._NET_CLOSE_WINDOW {
+            Some(ClientEvent::CloseWindow(window_id))
+        } else if message.type_ == atoms._NET_WM_STATE {
+            let action = match data[0] {
+                0 => StateAction::Remove,
+                1 => StateAction::Add,
+                _ => StateAction::Toggle,
+            };
+            if data[1..3].contains(&atoms._NET_WM_STATE_FULLSCREEN) {
+                Some(ClientEvent::Fullscreen(window_id, action))
+            } else if data[1..3].contains(&atoms._NET_WM_STATE_DEMANDS_ATTENTION) {
+                Some(ClientEvent::Attention(window_id, action))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+}
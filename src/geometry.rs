@@ -0,0 +1,251 @@
+use serde_derive::Deserialize;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Windows never get smaller than this, even if the layout runs out of room.
+pub const MIN_SIZE: i32 = 1;
+
+/// Screen rectangle. Position is signed so that windows may be placed partially
+/// off screen, all arithmetic saturates instead of overflowing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+pub fn to_i32(value: usize) -> i32 {
+    i32::try_from(value).unwrap_or(i32::MAX)
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Rect {
+        Rect {
+            x,
+            y,
+            w: w.max(MIN_SIZE),
+            h: h.max(MIN_SIZE),
+        }
+    }
+
+    pub fn from_size((w, h): (usize, usize)) -> Rect {
+        Rect::new(0, 0, to_i32(w), to_i32(h))
+    }
+
+    /// Removes `gap` from every side.
+    pub fn shrink(self, gap: i32) -> Rect {
+        Rect::new(
+            self.x.saturating_add(gap),
+            self.y.saturating_add(gap),
+            self.w.saturating_sub(gap.saturating_mul(2)),
+            self.h.saturating_sub(gap.saturating_mul(2)),
+        )
+    }
+
+    /// Removes reserved space from the edges.
+    pub fn reserve(self, struts: Struts) -> Rect {
+        let (top, bottom) = (to_i32(struts.top), to_i32(struts.bottom));
+        let (left, right) = (to_i32(struts.left), to_i32(struts.right));
+        Rect::new(
+            self.x.saturating_add(left),
+            self.y.saturating_add(top),
+            self.w.saturating_sub(left.saturating_add(right)),
+            self.h.saturating_sub(top.saturating_add(bottom)),
+        )
+    }
+
+    /// Splits into columns as wide as their share of `weights`, separated by `gap`.
+    pub fn split_columns_weighted(self, weights: &[u32], gap: i32) -> Vec<Rect> {
+        split_weighted(self.w, weights, gap)
+            .map(|(offset, w)| Rect::new(self.x.saturating_add(offset), self.y, w, self.h))
+            .collect()
+    }
+
+    /// Splits into rows as high as their share of `weights`, separated by `gap`.
+    pub fn split_rows_weighted(self, weights: &[u32], gap: i32) -> Vec<Rect> {
+        split_weighted(self.h, weights, gap)
+            .map(|(offset, h)| Rect::new(self.x, self.y.saturating_add(offset), self.w, h))
+            .collect()
+    }
+
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x.saturating_add(self.w)
+            && y < self.y.saturating_add(self.h)
+    }
+
+    pub fn translate(self, dx: i32, dy: i32) -> Rect {
+        Rect::new(
+            self.x.saturating_add(dx),
+            self.y.saturating_add(dy),
+            self.w,
+            self.h,
+        )
+    }
+
+    /// Moves the bottom right corner, keeping at least `MIN_SIZE`.
+    pub fn grow(self, dw: i32, dh: i32) -> Rect {
+        Rect::new(
+            self.x,
+            self.y,
+            self.w.saturating_add(dw),
+            self.h.saturating_add(dh),
+        )
+    }
+
+    /// Rectangle of `size` centered inside `self`, shrunk to fit if needed.
+    pub fn centered(self, (w, h): (usize, usize)) -> Rect {
+        let w = to_i32(w).min(self.w);
+        let h = to_i32(h).min(self.h);
+        Rect::new(
+            self.x.saturating_add(self.w.saturating_sub(w) / 2),
+            self.y.saturating_add(self.h.saturating_sub(h) / 2),
+            w,
+            h,
+        )
+    }
+
+    /// Rectangle of `size` at `anchor` inside `self`, shrunk to fit if needed.
+    pub fn anchored(self, (w, h): (Length, Length), anchor: Anchor) -> Rect {
+        let w = w.resolve(self.w).min(self.w);
+        let h = h.resolve(self.h).min(self.h);
+        let centered_x = self.x.saturating_add(self.w.saturating_sub(w) / 2);
+        let centered_y = self.y.saturating_add(self.h.saturating_sub(h) / 2);
+        let (x, y) = match anchor {
+            Anchor::Center => (centered_x, centered_y),
+            Anchor::Top => (centered_x, self.y),
+            Anchor::Right => (self.x.saturating_add(self.w.saturating_sub(w)), centered_y),
+        };
+        Rect::new(x, y, w, h)
+    }
+}
+
+/// Width or height in pixels, or relative to the screen like `50%`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "LengthValue")]
+pub enum Length {
+    Pixels(usize),
+    Percent(usize),
+}
+
+impl Length {
+    /// Length in pixels on a screen edge of `total` pixels.
+    pub fn resolve(self, total: i32) -> i32 {
+        match self {
+            Length::Pixels(pixels) => to_i32(pixels),
+            Length::Percent(percent) => {
+                let pixels = i64::from(total) * i64::try_from(percent).unwrap_or(i64::MAX) / 100;
+                i32::try_from(pixels).unwrap_or(i32::MAX)
+            }
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(length: &str) -> std::result::Result<Length, String> {
+        let (digits, percent) = match length.strip_suffix('%') {
+            Some(digits) => (digits, true),
+            None => (length, false),
+        };
+        match digits.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("`{}` is not a length like 800 or 50%", length)),
+            Ok(value) if percent && value > 100 => {
+                Err(format!("`{}` is more than the whole screen", length))
+            }
+            Ok(value) if percent => Ok(Length::Percent(value)),
+            Ok(value) => Ok(Length::Pixels(value)),
+        }
+    }
+}
+
+/// Config files may give lengths as numbers or strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LengthValue {
+    Number(usize),
+    Text(String),
+}
+
+impl TryFrom<LengthValue> for Length {
+    type Error = String;
+
+    fn try_from(value: LengthValue) -> std::result::Result<Length, String> {
+        match value {
+            LengthValue::Number(0) => Err("0 is not a length like 800 or 50%".to_string()),
+            LengthValue::Number(pixels) => Ok(Length::Pixels(pixels)),
+            LengthValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+/// Where a scratchpad sits on the screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    #[default]
+    Center,
+    /// Dropdown from the top edge
+    Top,
+    /// Sidebar on the right edge
+    Right,
+}
+
+impl FromStr for Anchor {
+    type Err = String;
+
+    fn from_str(anchor: &str) -> std::result::Result<Anchor, String> {
+        match anchor {
+            "CENTER" => Ok(Anchor::Center),
+            "TOP" => Ok(Anchor::Top),
+            "RIGHT" => Ok(Anchor::Right),
+            _ => Err(format!(
+                "unknown anchor `{}`, expected CENTER, TOP or RIGHT",
+                anchor
+            )),
+        }
+    }
+}
+
+/// Space reserved for panels and docks on every screen edge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Struts {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl Struts {
+    /// Per-edge maximum, so overlapping panels don't add up.
+    pub fn max(self, other: Struts) -> Struts {
+        Struts {
+            top: self.top.max(other.top),
+            bottom: self.bottom.max(other.bottom),
+            left: self.left.max(other.left),
+            right: self.right.max(other.right),
+        }
+    }
+}
+
+/// Offsets and lengths of the parts of `length` as long as their share of
+/// `weights`, with `gap` between them.
+fn split_weighted(length: i32, weights: &[u32], gap: i32) -> impl Iterator<Item = (i32, i32)> + '_ {
+    let count = to_i32(weights.len()).max(1);
+    let gaps = gap.saturating_mul(count - 1);
+    let free = i64::from(length.saturating_sub(gaps).max(0));
+    let total = weights.iter().map(|w| i64::from(*w)).sum::<i64>().max(1);
+    let mut offset = 0i32;
+    weights.iter().map(move |weight| {
+        let part = i32::try_from(free * i64::from(*weight) / total)
+            .unwrap_or(i32::MAX)
+            .max(MIN_SIZE);
+        let current = offset;
+        offset = offset.saturating_add(part).saturating_add(gap);
+        (current, part)
+    })
+}
@@ -1,18 +1,18 @@
-use serde_derive::Deserialize;
+mod config;
+pub mod event_handlers;
+pub mod ewmh;
+pub mod geometry;
+pub mod mutation;
+pub mod processor;
+pub mod reconciler;
+pub mod socket;
+
+pub use crate::config::{default_config_path, Config, ConfigError, FocusMode, ScratchpadConfig};
+use geometry::{to_i32, Anchor, Length, Rect, Struts};
+use std::fmt;
 use std::io::Result;
 use std::process::Command;
-
-#[derive(Deserialize)]
-pub struct Config {
-    pub workspaces: usize,
-    pub gaps: usize,
-    pub focused_border: String,
-    pub unfocused_border: String,
-    pub panel_width: usize,
-    pub scratchpad_size: (usize, usize),
-    // TODO read from xrandr
-    pub workspace_size: (usize, usize),
-}
+use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum WindowEventType {
@@ -23,13 +23,77 @@ pub enum WindowEventType {
     FocusOut,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(direction: &str) -> std::result::Result<Direction, String> {
+        match direction {
+            "LEFT" => Ok(Direction::Left),
+            "RIGHT" => Ok(Direction::Right),
+            "UP" => Ok(Direction::Up),
+            "DOWN" => Ok(Direction::Down),
+            _ => Err(format!(
+                "unknown direction `{}`, expected LEFT, RIGHT, UP or DOWN",
+                direction
+            )),
+        }
+    }
+}
+
+/// X window id, written the way wmutils prints it: `0x%08x`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowId(pub u32);
+
+impl fmt::Display for WindowId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:08x}", self.0)
+    }
+}
+
+impl fmt::Debug for WindowId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl FromStr for WindowId {
+    type Err = String;
+
+    fn from_str(window_id: &str) -> std::result::Result<WindowId, String> {
+        let digits = window_id
+            .strip_prefix("0x")
+            .or_else(|| window_id.strip_prefix("0X"))
+            .unwrap_or(window_id);
+        u32::from_str_radix(digits, 16)
+            .map(WindowId)
+            .map_err(|_| format!("`{}` is not a window id like 0x01a00003", window_id))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum WorkspaceEvent {
     MoveWindow(usize),
     Focus(usize),
-    FocusWindow(String),
+    FocusWindow(Direction),
+    Swap(Direction),
+    /// Move the focused window's edge in `Direction` by that many pixels,
+    /// negative values shrink it
+    Resize(Direction, i32),
+    Promote,
     FullscreenToggle,
     Cycle,
+    FocusUrgent,
+    FocusNext,
+    FocusPrev,
+    FocusLast,
 }
 
 #[derive(Debug, Clone)]
@@ -37,11 +101,72 @@ pub enum ScratchpadEvent {
     AddWindow(String),
     RemoveWindow(String),
     ToggleWindow(String),
+    /// Return every scratchpad window to the focused workspace
+    RestoreAll,
+    /// New size and optionally anchor of a scratchpad
+    Resize(String, (Length, Length), Option<Anchor>),
+}
+
+/// `_NET_WM_STATE` change requested by a client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateAction {
+    Remove,
+    Add,
+    Toggle,
+}
+
+/// Requests other clients send to the root window, see `ewmh::Ewmh::next_client_event`.
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    CurrentDesktop(usize),
+    ActiveWindow(WindowId),
+    WindowDesktop(WindowId, usize),
+    CloseWindow(WindowId),
+    Fullscreen(WindowId, StateAction),
+    Attention(WindowId, StateAction),
+    /// A window changed the screen edges it reserves
+    Struts(WindowId, Option<Struts>),
+}
+
+/// Commands for the focused window.
+#[derive(Debug, Clone)]
+pub enum WindowCommand {
+    Close,
+    FloatToggle,
+    /// Show on every workspace, floating above tiled windows
+    StickyToggle,
+}
+
+/// super+drag pointer events, coordinates are relative to the root window.
+#[derive(Debug, Clone)]
+pub enum PointerEvent {
+    Press {
+        window_id: WindowId,
+        button: u8,
+        x: i32,
+        y: i32,
+    },
+    Motion {
+        x: i32,
+        y: i32,
+    },
+    Release {
+        x: i32,
+        y: i32,
+    },
+}
+
+/// Commands that only answer the client that sent them and don't change the state.
+#[derive(Debug, Clone)]
+pub enum QueryEvent {
+    State,
+    /// Scratchpads with their window and whether they are shown
+    Scratchpads,
 }
 
 #[derive(Debug, Clone)]
 pub struct WindowEvent {
-    pub window_id: String,
+    pub window_id: WindowId,
     pub event_type: WindowEventType,
 }
 
@@ -50,15 +175,24 @@ pub enum Event {
     Window(WindowEvent),
     Workspace(WorkspaceEvent),
     Scratchpad(ScratchpadEvent),
+    Client(ClientEvent),
+    Command(WindowCommand),
+    Pointer(PointerEvent),
+    Query(QueryEvent),
+    /// Known command with bad arguments
+    Invalid(String),
     Unknown,
 }
 
 pub fn parse_event(ev_str: Result<String>) -> Event {
-    let ev_str_parts: Vec<String> = ev_str
-        .unwrap()
-        .split_whitespace()
-        .map(ToOwned::to_owned)
-        .collect();
+    let ev_str = match ev_str {
+        Ok(ev_str) => ev_str,
+        Err(err) => return Event::Invalid(format!("can't read event: {}", err)),
+    };
+    let ev_str_parts: Vec<String> = ev_str.split_whitespace().map(ToOwned::to_owned).collect();
+    if ev_str_parts.is_empty() {
+        return Event::Unknown;
+    }
     if ["FOCUS_OUT", "CREATE", "DESTROY", "MAP", "FOCUS_IN"].contains(&ev_str_parts[0].as_str()) {
         let event_type = match ev_str_parts[0].as_str() {
             "CREATE" => WindowEventType::CreateNotify,
@@ -68,84 +202,146 @@ pub fn parse_event(ev_str: Result<String>) -> Event {
             "FOCUS_OUT" => WindowEventType::FocusOut,
             _ => unreachable!(),
         };
-        Event::Window(WindowEvent {
-            window_id: ev_str_parts[1].to_owned(),
-            event_type,
-        })
+        match ev_str_parts.get(1).map(|window_id| window_id.parse()) {
+            Some(Ok(window_id)) => Event::Window(WindowEvent {
+                window_id,
+                event_type,
+            }),
+            Some(Err(err)) => Event::Invalid(format!("{}: {}", ev_str_parts[0], err)),
+            None => Event::Invalid(format!("{} takes window id", ev_str_parts[0])),
+        }
     } else if [
         "WS_FOCUS",
         "WS_MOVE",
         "WS_CYCLE",
         "WS_FULLSCREEN",
         "WS_FOCUS_WINDOW",
+        "WS_SWAP",
+        "WS_RESIZE",
+        "WS_PROMOTE",
     ]
     .contains(&ev_str_parts[0].as_str())
     {
-        if ev_str_parts[0].as_str() == "WS_FOCUS_WINDOW" {
-            return Event::Workspace(WorkspaceEvent::FocusWindow(ev_str_parts[1].clone()));
+        if ["WS_FOCUS_WINDOW", "WS_SWAP", "WS_RESIZE"].contains(&ev_str_parts[0].as_str()) {
+            let direction = match ev_str_parts.get(1).map(|d| d.parse::<Direction>()) {
+                Some(Ok(direction)) => direction,
+                Some(Err(err)) => return Event::Invalid(format!("{}: {}", ev_str_parts[0], err)),
+                None => return Event::Invalid(format!("{} takes direction", ev_str_parts[0])),
+            };
+            return match ev_str_parts[0].as_str() {
+                "WS_FOCUS_WINDOW" => Event::Workspace(WorkspaceEvent::FocusWindow(direction)),
+                "WS_RESIZE" => match ev_str_parts.get(2).map(|px| px.parse::<i32>()) {
+                    Some(Ok(px)) => Event::Workspace(WorkspaceEvent::Resize(direction, px)),
+                    _ => Event::Invalid("WS_RESIZE takes direction and pixels".to_string()),
+                },
+                _ => Event::Workspace(WorkspaceEvent::Swap(direction)),
+            };
+        }
+        let workspace: Option<usize> = ev_str_parts
+            .get(1)
+            .and_then(|ws| ws.parse().ok())
+            .filter(|ws| *ws > 0);
+        match (ev_str_parts[0].as_str(), workspace) {
+            ("WS_FOCUS", Some(ws)) => Event::Workspace(WorkspaceEvent::Focus(ws - 1)),
+            ("WS_MOVE", Some(ws)) => Event::Workspace(WorkspaceEvent::MoveWindow(ws - 1)),
+            ("WS_FOCUS", None) | ("WS_MOVE", None) => {
+                Event::Invalid(format!("{} takes workspace number", ev_str_parts[0]))
+            }
+            ("WS_CYCLE", _) => Event::Workspace(WorkspaceEvent::Cycle),
+            ("WS_FULLSCREEN", _) => Event::Workspace(WorkspaceEvent::FullscreenToggle),
+            ("WS_PROMOTE", _) => Event::Workspace(WorkspaceEvent::Promote),
+            _ => unreachable!(),
+        }
+    } else if ev_str_parts[0] == "SP_RESIZE" {
+        match parse_scratchpad_resize(&ev_str_parts[1..]) {
+            Ok(event) => Event::Scratchpad(event),
+            Err(err) => Event::Invalid(format!("SP_RESIZE: {}", err)),
         }
-        let workspace: Option<usize> = if ev_str_parts.len() < 2 {
-            None
-        } else {
-            ev_str_parts[1].parse().ok()
+    } else if ["SP_ADD", "SP_REMOVE", "SP_TOGGLE"].contains(&ev_str_parts[0].as_str()) {
+        let name = match ev_str_parts.get(1) {
+            Some(name) => name.clone(),
+            None => return Event::Invalid(format!("{} takes scratchpad name", ev_str_parts[0])),
         };
         match ev_str_parts[0].as_str() {
-            "WS_FOCUS" => Event::Workspace(WorkspaceEvent::Focus(
-                workspace.expect("WS_FOCUS event takes workspace argument") - 1,
-            )),
-            "WS_MOVE" => Event::Workspace(WorkspaceEvent::MoveWindow(
-                workspace.expect("WS_MOVE event takes workspace argument") - 1,
-            )),
-            "WS_CYCLE" => Event::Workspace(WorkspaceEvent::Cycle),
-            "WS_FULLSCREEN" => Event::Workspace(WorkspaceEvent::FullscreenToggle),
+            "SP_ADD" => Event::Scratchpad(ScratchpadEvent::AddWindow(name)),
+            "SP_REMOVE" => Event::Scratchpad(ScratchpadEvent::RemoveWindow(name)),
+            "SP_TOGGLE" => Event::Scratchpad(ScratchpadEvent::ToggleWindow(name)),
             _ => unreachable!(),
         }
-    } else if ["SP_ADD", "SP_REMOVE", "SP_TOGGLE"].contains(&ev_str_parts[0].as_str()) {
+    } else if ev_str_parts[0] == "SP_RESTORE_ALL" {
+        Event::Scratchpad(ScratchpadEvent::RestoreAll)
+    } else if ev_str_parts[0] == "SP_LIST" {
+        Event::Query(QueryEvent::Scratchpads)
+    } else if ["FOCUS_URGENT", "FOCUS_NEXT", "FOCUS_PREV", "FOCUS_LAST"]
+        .contains(&ev_str_parts[0].as_str())
+    {
         match ev_str_parts[0].as_str() {
-            "SP_ADD" => Event::Scratchpad(ScratchpadEvent::AddWindow(ev_str_parts[1].clone())),
-            "SP_REMOVE" => {
-                Event::Scratchpad(ScratchpadEvent::RemoveWindow(ev_str_parts[1].clone()))
-            }
-            "SP_TOGGLE" => {
-                Event::Scratchpad(ScratchpadEvent::ToggleWindow(ev_str_parts[1].clone()))
-            }
+            "FOCUS_URGENT" => Event::Workspace(WorkspaceEvent::FocusUrgent),
+            "FOCUS_NEXT" => Event::Workspace(WorkspaceEvent::FocusNext),
+            "FOCUS_PREV" => Event::Workspace(WorkspaceEvent::FocusPrev),
+            "FOCUS_LAST" => Event::Workspace(WorkspaceEvent::FocusLast),
+            _ => unreachable!(),
+        }
+    } else if [
+        "WINDOW_CLOSE",
+        "WINDOW_FLOAT_TOGGLE",
+        "WINDOW_STICKY_TOGGLE",
+    ]
+    .contains(&ev_str_parts[0].as_str())
+    {
+        match ev_str_parts[0].as_str() {
+            "WINDOW_CLOSE" => Event::Command(WindowCommand::Close),
+            "WINDOW_FLOAT_TOGGLE" => Event::Command(WindowCommand::FloatToggle),
+            "WINDOW_STICKY_TOGGLE" => Event::Command(WindowCommand::StickyToggle),
             _ => unreachable!(),
         }
+    } else if ev_str_parts[0].as_str() == "QUERY_STATE" {
+        Event::Query(QueryEvent::State)
     } else {
         Event::Unknown
     }
 }
 
-pub fn focus_window(window_id: impl Into<String>) {
-    Command::new("wtf").arg(window_id.into()).status().ok();
+/// `name width height [anchor]` arguments of `SP_RESIZE`.
+fn parse_scratchpad_resize(args: &[String]) -> std::result::Result<ScratchpadEvent, String> {
+    let (name, w, h, anchor) = match args {
+        [name, w, h] => (name, w, h, None),
+        [name, w, h, anchor] => (name, w, h, Some(anchor.parse::<Anchor>()?)),
+        _ => return Err("takes name, width, height and optionally anchor".to_string()),
+    };
+    Ok(ScratchpadEvent::Resize(
+        name.clone(),
+        (w.parse()?, h.parse()?),
+        anchor,
+    ))
 }
 
-pub fn foreground_window(window_id: impl Into<String>) {
+pub fn focus_window(window_id: WindowId) {
+    Command::new("wtf").arg(window_id.to_string()).status().ok();
+}
+
+pub fn foreground_window(window_id: WindowId) {
     Command::new("chwso")
         .arg("-r")
-        .arg(window_id.into())
+        .arg(window_id.to_string())
         .status()
         .ok();
 }
 
-pub fn fullscreen_window(window_id: impl Into<String> + Clone, (w, h): (usize, usize)) {
+pub fn fullscreen_window(window_id: WindowId, screen: Rect) {
     Command::new("chwb")
         .arg("-s")
         .arg("0")
-        .arg(window_id.clone().into())
-        .status()
-        .ok();
-    Command::new("wtp")
-        .args(&["0", "0", &w.to_string(), &h.to_string()])
-        .arg(window_id.into())
+        .arg(window_id.to_string())
         .status()
         .ok();
+    move_window(window_id, screen);
 }
 
-pub fn window_type(window_id: impl Into<String>) -> Option<String> {
+pub fn window_type(window_id: WindowId) -> Option<String> {
     let output = Command::new("xprop")
         .arg("-id")
-        .arg(window_id.into())
+        .arg(window_id.to_string())
         .arg("_NET_WM_WINDOW_TYPE")
         .output()
         .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
@@ -160,10 +356,27 @@ pub fn window_type(window_id: impl Into<String>) -> Option<String> {
     None
 }
 
-pub fn window_redirect_override(window_id: impl Into<String>) -> bool {
+/// Kills the window's client with wmutils, for when there is no X connection of our own.
+pub fn kill_window(window_id: WindowId) {
+    Command::new("killw")
+        .arg(window_id.to_string())
+        .status()
+        .ok();
+}
+
+/// Runs `command` with `sh` in the background, so the window manager doesn't wait for it.
+pub fn spawn(command: &str) {
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} &", command))
+        .status()
+        .ok();
+}
+
+pub fn window_redirect_override(window_id: WindowId) -> bool {
     if let Ok(status) = Command::new("wattr")
         .arg("o")
-        .arg(window_id.into())
+        .arg(window_id.to_string())
         .status()
     {
         if status.success() {
@@ -172,8 +385,8 @@ pub fn window_redirect_override(window_id: impl Into<String>) -> bool {
     }
     false
 }
-pub fn is_ignored(window_id: impl Into<String> + Clone) -> bool {
-    if let Some(typ) = window_type(window_id.clone().into().as_str()) {
+pub fn is_ignored(window_id: WindowId) -> bool {
+    if let Some(typ) = window_type(window_id) {
         if [
             "_NET_WM_WINDOW_TYPE_DOCK",
             "_NET_WM_WINDOW_TYPE_UTILITY",
@@ -193,80 +406,73 @@ pub fn is_ignored(window_id: impl Into<String> + Clone) -> bool {
     false
 }
 
-pub fn map_window(window_id: impl Into<String>) {
+pub fn map_window(window_id: WindowId) {
     Command::new("mapw")
         .arg("-m")
-        .arg(window_id.into())
+        .arg(window_id.to_string())
         .status()
         .ok();
 }
 
-pub fn unmap_window(window_id: impl Into<String>) {
+pub fn unmap_window(window_id: WindowId) {
     Command::new("mapw")
         .arg("-u")
-        .arg(window_id.into())
+        .arg(window_id.to_string())
         .status()
         .ok();
 }
 
-pub fn border_window(window_id: impl Into<String>, color: impl Into<String>) {
+pub fn border_window(window_id: WindowId, color: impl Into<String>) {
     Command::new("chwb")
         .arg("-c")
         .arg(color.into())
         .arg("-s")
         .arg("3")
-        .arg(window_id.into())
+        .arg(window_id.to_string())
         .status()
         .ok();
 }
 
-pub fn move_window(window_id: impl Into<String>, x: usize, y: usize, w: usize, h: usize) {
+pub fn move_window(window_id: WindowId, rect: Rect) {
     Command::new("wtp")
         .args(
-            vec![x, y, w, h]
+            [rect.x, rect.y, rect.w, rect.h]
                 .iter()
                 .map(|i| format!("{}", i))
                 .collect::<Vec<String>>(),
         )
-        .arg(window_id.into())
+        .arg(window_id.to_string())
         .status()
         .ok();
 }
 
-pub fn tile_windows(
-    windows: Vec<String>,
+/// Weight of a tiled window that was never resized.
+pub const DEFAULT_WEIGHT: u32 = 100;
+/// Thousandths of the width the left column gets unless resized.
+pub const DEFAULT_COLUMN_SHARE: u32 = 500;
+
+/// Master-less two column layout: the left column gets half of the windows,
+/// the right one the rest, a single window takes the whole `area`. Rows get
+/// heights proportional to their window's weight and the left column gets
+/// `column_share` thousandths of the width.
+pub fn weighted_tile_layout(
+    weights: &[u32],
+    column_share: u32,
     gap: usize,
-    (wsw, wsh): (usize, usize),
-    panel_size: usize,
-) {
-    match windows.len() {
-        0 => {}
-        1 => {
-            let full_w = wsw - 2 * gap;
-            let full_h = wsh - 2 * gap - panel_size;
-            move_window(&windows[0], gap, gap, full_w, full_h);
-        }
+    area: Rect,
+) -> Vec<Rect> {
+    let gap = to_i32(gap);
+    let inner = area.shrink(gap);
+    match weights.len() {
+        0 => Vec::new(),
+        1 => vec![inner],
         n => {
-            let half_w = (wsw - 3 * gap) / 2;
-            let left_n = n / 2;
-            let right_n = n - left_n;
-            let left_h = (wsh - (left_n + 1) * gap - panel_size) / left_n;
-            let right_h = (wsh - (right_n + 1) * gap - panel_size) / right_n;
-            let mut left_strip = windows;
-            let right_strip = left_strip.split_off(left_n);
-
-            for (i, wid) in left_strip.iter().enumerate() {
-                move_window(wid, gap, gap * (i + 1) + left_h * i, half_w, left_h);
-            }
-            for (i, wid) in right_strip.iter().enumerate() {
-                move_window(
-                    wid,
-                    half_w + gap * 2,
-                    gap * (i + 1) + right_h * i,
-                    half_w,
-                    right_h,
-                );
-            }
+            let (left, right) = weights.split_at(n / 2);
+            let column_share = column_share.min(1000);
+            let columns = inner.split_columns_weighted(&[column_share, 1000 - column_share], gap);
+            let mut rects = columns[0].split_rows_weighted(left, gap);
+            rects.extend(columns[1].split_rows_weighted(right, gap));
+            rects
         }
     }
 }
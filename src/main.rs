@@ -1,50 +1,85 @@
-mod event_handlers;
-mod reconciler;
+use ::derpywm::ewmh::Ewmh;
+use ::derpywm::processor::{EventProcessor, X11Backend};
+use ::derpywm::socket::{self, default_socket_path};
+use ::derpywm::{parse_event, Config};
 
-use ::derpywm::{parse_event, Config, Event};
-use reconciler::{actualize_screen, WMState};
-
-use event_handlers::*;
 use std::io::{self, BufRead};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::thread;
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        panic!("Provide config path");
+    let mut args: Vec<String> = std::env::args().collect();
+    let verbose = args.iter().any(|arg| arg == "--verbose");
+    args.retain(|arg| arg != "--verbose");
+    if args.len() > 2 {
+        eprintln!("Usage: {} [--verbose] [config path]", args[0]);
+        std::process::exit(2);
     }
-    let config_file = std::fs::read_to_string(args[1].clone()).expect("Can't find config.toml");
-    let config: Config = toml::from_str(config_file.as_str()).expect("Can't parse config file");
-
-    let mut last_event = Event::Unknown;
-    let mut last_state = WMState::new(config.workspaces);
-
-    for event in io::stdin().lock().lines().map(parse_event) {
-        let mut now = last_state.clone();
-        let event_clone = event.clone();
-
-        dbg!(event.clone());
+    let config = match Config::load(args.get(1).map(PathBuf::from)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("derpy-wm: {}", err);
+            std::process::exit(1);
+        }
+    };
 
-        match event {
-            Event::Window(event) => {
-                if !handle_window_event(&mut now, event, &last_event) {
-                    continue;
+    let (sender, events) = mpsc::channel();
+    let socket_path = config.socket.clone().unwrap_or_else(default_socket_path);
+    if let Err(err) = socket::listen(&socket_path, sender.clone()) {
+        eprintln!(
+            "derpy-wm: can't listen on {}: {}",
+            socket_path.display(),
+            err
+        );
+    }
+    let ewmh = Ewmh::connect(config.focus_mode).map(Arc::new);
+    if let Some(ewmh) = &ewmh {
+        let reaper = Arc::clone(ewmh);
+        thread::spawn(move || reaper.kill_hung_clients());
+        let ewmh = Arc::clone(ewmh);
+        let sender = sender.clone();
+        thread::spawn(move || {
+            while let Some(event) = ewmh.next_event() {
+                if sender.send((event, None)).is_err() {
+                    break;
                 }
             }
-            Event::Workspace(event) => {
-                if !handle_workspace_event(&mut now, event) {
-                    continue;
-                }
+        });
+    } else {
+        eprintln!("derpy-wm: can't connect to X server, EWMH hints are disabled");
+    }
+    thread::spawn(move || {
+        for event in io::stdin().lock().lines().map(parse_event) {
+            if sender.send((event, None)).is_err() {
+                break;
             }
-            Event::Scratchpad(event) => {
-                if !handle_scratchpad_event(&mut now, event) {
-                    continue;
+        }
+    });
+
+    let mut processor = EventProcessor::new(config, X11Backend::new(ewmh));
+    if verbose {
+        processor.add_pre_hook(|event, _| eprintln!("derpy-wm: {:?}", event));
+    }
+    for (event, reply) in events {
+        let result = processor.process(event);
+        if let Err(err) = &result {
+            eprintln!("derpy-wm: {}", err);
+        }
+        match (reply, result) {
+            (Some(reply), Ok(changes)) => {
+                reply.send(changes.reply.unwrap_or_default()).ok();
+            }
+            (Some(reply), Err(err)) => {
+                reply.send(format!("error: {}", err)).ok();
+            }
+            // Queries typed into stdin are answered on stdout
+            (None, Ok(changes)) => {
+                if let Some(answer) = changes.reply {
+                    println!("{}", answer);
                 }
             }
-            Event::Unknown => continue,
+            (None, Err(_)) => {}
         }
-
-        now.focused_window = actualize_screen(&last_state, &now, &config);
-        last_event = event_clone;
-        last_state = now;
     }
 }
@@ -0,0 +1,397 @@
+use crate::geometry::{Rect, Struts};
+use crate::reconciler::{
+    Drag, ScratchpadGeometry, ScratchpadName, ScratchpadState, WMState, WindowId, WorkspaceState,
+};
+
+use std::ops::Deref;
+
+/// One change to `WMState`. Handlers make every change through these, so the
+/// reconciler knows what to update on screen without comparing whole states.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mutation {
+    FocusWorkspace {
+        from: usize,
+        to: usize,
+    },
+    FocusWindow(Option<WindowId>),
+    /// Appends the window to the workspace and its focus history
+    AddWindow {
+        workspace: usize,
+        window: WindowId,
+    },
+    /// Drops the window with its floating geometry and weight, fullscreen is cleared separately
+    RemoveWindow {
+        workspace: usize,
+        window: WindowId,
+    },
+    /// Moves the window to the end of the workspace's focus history
+    FocusOnWorkspace {
+        workspace: usize,
+        window: WindowId,
+    },
+    /// Reorders the workspace's windows, `windows` has the same ones as before
+    OrderWindows {
+        workspace: usize,
+        windows: Vec<WindowId>,
+    },
+    Fullscreen {
+        workspace: usize,
+        from: Option<WindowId>,
+        to: Option<WindowId>,
+    },
+    /// Floating geometry, `None` tiles the window
+    Float {
+        workspace: usize,
+        window: WindowId,
+        from: Option<Rect>,
+        to: Option<Rect>,
+    },
+    Weight {
+        workspace: usize,
+        window: WindowId,
+        weight: u32,
+    },
+    ColumnShare {
+        workspace: usize,
+        share: u32,
+    },
+    AddScratchpad {
+        name: ScratchpadName,
+        window: WindowId,
+    },
+    /// Forgets the scratchpad and hides it, its geometry stays
+    RemoveScratchpad {
+        name: ScratchpadName,
+        window: WindowId,
+    },
+    /// Shows the scratchpad on top of the other shown ones
+    ShowScratchpad(ScratchpadName),
+    HideScratchpad(ScratchpadName),
+    Pending {
+        name: ScratchpadName,
+        pending: bool,
+    },
+    ScratchpadGeometry {
+        name: ScratchpadName,
+        geometry: ScratchpadGeometry,
+    },
+    /// Sticky geometry, `None` makes the window an ordinary one again
+    Sticky {
+        window: WindowId,
+        rect: Option<Rect>,
+    },
+    DockStruts {
+        window: WindowId,
+        struts: Option<Struts>,
+    },
+    Urgent {
+        window: WindowId,
+        urgent: bool,
+    },
+    Drag(Option<Drag>),
+    /// Window waiting for its first map, cleared by the map or its destruction
+    Created {
+        window: WindowId,
+        created: bool,
+    },
+    /// The config changed, everything on screen gets laid out again
+    Reconfigure,
+}
+
+/// What the state looked like before a transaction, for deciding where focus goes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Before {
+    pub focused_workspace: usize,
+    pub focused_window: Option<WindowId>,
+    pub focus_target: Option<WindowId>,
+}
+
+impl Before {
+    pub fn new(state: &WMState) -> Before {
+        Before {
+            focused_workspace: state.focused_workspace,
+            focused_window: state.focused_window,
+            focus_target: state.focus_target(),
+        }
+    }
+}
+
+/// Parts of the state as they were before the transaction first changed them.
+#[derive(Default)]
+struct Originals {
+    workspaces: Vec<(usize, WorkspaceState)>,
+    scratchpad: Option<ScratchpadState>,
+    focused_workspace: Option<usize>,
+    focused_window: Option<Option<WindowId>>,
+    dock_struts: Option<Vec<(WindowId, Struts)>>,
+    urgent: Option<Vec<WindowId>>,
+    drag: Option<Option<Drag>>,
+    sticky: Option<Vec<(WindowId, Rect)>>,
+    created: Option<Vec<WindowId>>,
+}
+
+/// Applies mutations to the state and records them, `rollback` restores
+/// everything they touched.
+pub struct Transaction<'a> {
+    state: &'a mut WMState,
+    before: Before,
+    mutations: Vec<Mutation>,
+    originals: Originals,
+}
+
+impl Deref for Transaction<'_> {
+    type Target = WMState;
+
+    fn deref(&self) -> &WMState {
+        self.state
+    }
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new(state: &'a mut WMState) -> Transaction<'a> {
+        Transaction {
+            before: Before::new(state),
+            state,
+            mutations: Vec::new(),
+            originals: Originals::default(),
+        }
+    }
+
+    pub fn before(&self) -> &Before {
+        &self.before
+    }
+
+    pub fn mutations(&self) -> &[Mutation] {
+        &self.mutations
+    }
+
+    pub fn apply(&mut self, mutation: Mutation) {
+        self.save(&mutation);
+        apply(self.state, &mutation);
+        self.mutations.push(mutation);
+    }
+
+    /// Sets the workspace's fullscreen window unless it already is.
+    pub fn set_fullscreen(&mut self, workspace: usize, to: Option<WindowId>) {
+        let from = self.state.workspaces[workspace].fullscreen;
+        if from != to {
+            self.apply(Mutation::Fullscreen {
+                workspace,
+                from,
+                to,
+            });
+        }
+    }
+
+    /// Floats the window at `to` or tiles it with `None`.
+    pub fn set_floating(&mut self, workspace: usize, window: WindowId, to: Option<Rect>) {
+        let from = self.state.workspaces[workspace].floating_rect(window);
+        if from != to {
+            self.apply(Mutation::Float {
+                workspace,
+                window,
+                from,
+                to,
+            });
+        }
+    }
+
+    /// Keeps the changes and returns them.
+    pub fn commit(self) -> Vec<Mutation> {
+        self.mutations
+    }
+
+    /// Puts back everything the mutations touched.
+    pub fn rollback(self) {
+        let originals = self.originals;
+        let state = self.state;
+        for (i, workspace) in originals.workspaces {
+            state.workspaces[i] = workspace;
+        }
+        if let Some(scratchpad) = originals.scratchpad {
+            state.scratchpad = scratchpad;
+        }
+        if let Some(focused_workspace) = originals.focused_workspace {
+            state.focused_workspace = focused_workspace;
+        }
+        if let Some(focused_window) = originals.focused_window {
+            state.focused_window = focused_window;
+        }
+        if let Some(dock_struts) = originals.dock_struts {
+            state.dock_struts = dock_struts;
+        }
+        if let Some(urgent) = originals.urgent {
+            state.urgent = urgent;
+        }
+        if let Some(drag) = originals.drag {
+            state.drag = drag;
+        }
+        if let Some(sticky) = originals.sticky {
+            state.sticky = sticky;
+        }
+        if let Some(created) = originals.created {
+            state.created = created;
+        }
+    }
+
+    fn save(&mut self, mutation: &Mutation) {
+        let state = &*self.state;
+        let originals = &mut self.originals;
+        match mutation {
+            Mutation::FocusWorkspace { .. } => {
+                originals
+                    .focused_workspace
+                    .get_or_insert(state.focused_workspace);
+            }
+            Mutation::FocusWindow(_) => {
+                originals.focused_window.get_or_insert(state.focused_window);
+            }
+            Mutation::AddWindow { workspace, .. }
+            | Mutation::RemoveWindow { workspace, .. }
+            | Mutation::FocusOnWorkspace { workspace, .. }
+            | Mutation::OrderWindows { workspace, .. }
+            | Mutation::Fullscreen { workspace, .. }
+            | Mutation::Float { workspace, .. }
+            | Mutation::Weight { workspace, .. }
+            | Mutation::ColumnShare { workspace, .. } => {
+                if !originals.workspaces.iter().any(|(i, _)| i == workspace) {
+                    originals
+                        .workspaces
+                        .push((*workspace, state.workspaces[*workspace].clone()));
+                }
+            }
+            Mutation::AddScratchpad { .. }
+            | Mutation::RemoveScratchpad { .. }
+            | Mutation::ShowScratchpad(_)
+            | Mutation::HideScratchpad(_)
+            | Mutation::Pending { .. }
+            | Mutation::ScratchpadGeometry { .. } => {
+                originals
+                    .scratchpad
+                    .get_or_insert_with(|| state.scratchpad.clone());
+            }
+            Mutation::Sticky { .. } => {
+                originals.sticky.get_or_insert_with(|| state.sticky.clone());
+            }
+            Mutation::DockStruts { .. } => {
+                originals
+                    .dock_struts
+                    .get_or_insert_with(|| state.dock_struts.clone());
+            }
+            Mutation::Urgent { .. } => {
+                originals.urgent.get_or_insert_with(|| state.urgent.clone());
+            }
+            Mutation::Drag(_) => {
+                originals.drag.get_or_insert_with(|| state.drag.clone());
+            }
+            Mutation::Created { .. } => {
+                originals
+                    .created
+                    .get_or_insert_with(|| state.created.clone());
+            }
+            Mutation::Reconfigure => {}
+        }
+    }
+}
+
+fn apply(state: &mut WMState, mutation: &Mutation) {
+    match mutation {
+        Mutation::FocusWorkspace { to, .. } => state.focused_workspace = *to,
+        Mutation::FocusWindow(window) => state.focused_window = *window,
+        Mutation::AddWindow { workspace, window } => {
+            let workspace = &mut state.workspaces[*workspace];
+            workspace.windows.push(*window);
+            workspace.focus_history.push(*window);
+        }
+        Mutation::RemoveWindow { workspace, window } => {
+            let workspace = &mut state.workspaces[*workspace];
+            workspace.windows.retain(|wid| wid != window);
+            workspace.focus_history.retain(|wid| wid != window);
+            workspace.floating.retain(|(wid, _)| wid != window);
+            workspace.weights.retain(|(wid, _)| wid != window);
+        }
+        Mutation::FocusOnWorkspace { workspace, window } => {
+            let history = &mut state.workspaces[*workspace].focus_history;
+            history.retain(|wid| wid != window);
+            history.push(*window);
+        }
+        Mutation::OrderWindows { workspace, windows } => {
+            state.workspaces[*workspace].windows = windows.clone();
+        }
+        Mutation::Fullscreen { workspace, to, .. } => {
+            state.workspaces[*workspace].fullscreen = *to;
+        }
+        Mutation::Float {
+            workspace,
+            window,
+            to,
+            ..
+        } => {
+            let floating = &mut state.workspaces[*workspace].floating;
+            match (floating.iter_mut().find(|(wid, _)| wid == window), to) {
+                (Some((_, rect)), Some(to)) => *rect = *to,
+                (None, Some(to)) => floating.push((*window, *to)),
+                (_, None) => floating.retain(|(wid, _)| wid != window),
+            }
+        }
+        Mutation::Weight {
+            workspace,
+            window,
+            weight,
+        } => state.workspaces[*workspace].set_weight(*window, *weight),
+        Mutation::ColumnShare { workspace, share } => {
+            state.workspaces[*workspace].column_share = *share;
+        }
+        Mutation::AddScratchpad { name, window } => {
+            state.scratchpad.windows.push((name.clone(), *window));
+        }
+        Mutation::RemoveScratchpad { name, .. } => {
+            state.scratchpad.windows.retain(|(sname, _)| sname != name);
+            state.scratchpad.shown.retain(|shown| shown != name);
+        }
+        Mutation::ShowScratchpad(name) => {
+            state.scratchpad.shown.retain(|shown| shown != name);
+            state.scratchpad.shown.push(name.clone());
+        }
+        Mutation::HideScratchpad(name) => {
+            state.scratchpad.shown.retain(|shown| shown != name);
+        }
+        Mutation::Pending { name, pending } => {
+            state.scratchpad.pending.retain(|pname| pname != name);
+            if *pending {
+                state.scratchpad.pending.push(name.clone());
+            }
+        }
+        Mutation::ScratchpadGeometry { name, geometry } => {
+            state.scratchpad.geometry.retain(|(gname, _)| gname != name);
+            state.scratchpad.geometry.push((name.clone(), *geometry));
+        }
+        Mutation::Sticky { window, rect } => {
+            match (state.sticky.iter_mut().find(|(wid, _)| wid == window), rect) {
+                (Some((_, sticky)), Some(rect)) => *sticky = *rect,
+                (None, Some(rect)) => state.sticky.push((*window, *rect)),
+                (_, None) => state.sticky.retain(|(wid, _)| wid != window),
+            }
+        }
+        Mutation::DockStruts { window, struts } => {
+            state.dock_struts.retain(|(wid, _)| wid != window);
+            if let Some(struts) = struts {
+                state.dock_struts.push((*window, *struts));
+            }
+        }
+        Mutation::Urgent { window, urgent } => {
+            state.urgent.retain(|wid| wid != window);
+            if *urgent {
+                state.urgent.push(*window);
+            }
+        }
+        Mutation::Drag(drag) => state.drag = drag.clone(),
+        Mutation::Created { window, created } => {
+            state.created.retain(|wid| wid != window);
+            if *created {
+                state.created.push(*window);
+            }
+        }
+        Mutation::Reconfigure => {}
+    }
+}
@@ -0,0 +1,324 @@
+use crate::event_handlers::*;
+use crate::ewmh::Ewmh;
+use crate::geometry::Struts;
+use crate::mutation::{Mutation, Transaction};
+use crate::reconciler::{
+    actualize_ewmh, focused_window, publish_ewmh, reconcile, run_ops, InvariantError, Op, WMState,
+};
+use crate::{is_ignored, kill_window, spawn, Config, Event, WindowId};
+
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where state changes end up and where handlers learn about windows,
+/// the X server or nothing at all in tests.
+pub trait Backend {
+    /// Called once with the initial state.
+    fn start(&mut self, _state: &WMState) {}
+
+    /// Carries out `ops` and publishes what `mutations` changed in `state`.
+    fn apply(&mut self, ops: &[Op], mutations: &[Mutation], state: &WMState, config: &Config);
+
+    /// Whether the window is a dock, menu or similar that isn't tiled.
+    fn is_ignored(&self, _window_id: WindowId) -> bool {
+        false
+    }
+
+    /// Edges the window reserves with `_NET_WM_STRUT_PARTIAL` or `_NET_WM_STRUT`.
+    fn window_struts(&self, _window_id: WindowId) -> Option<Struts> {
+        None
+    }
+
+    /// Whether the window asks for attention with its urgency hint or `_NET_WM_STATE`.
+    fn is_urgent(&self, _window_id: WindowId) -> bool {
+        false
+    }
+
+    /// Instance and class names from `WM_CLASS`.
+    fn window_class(&self, _window_id: WindowId) -> Option<(String, String)> {
+        None
+    }
+
+    /// Starts `command` in the background.
+    fn spawn(&mut self, _command: &str) {}
+
+    /// Asks the window to close, its `DestroyNotify` updates the state.
+    fn close_window(&mut self, _window_id: WindowId, _config: &Config) {}
+}
+
+/// Drives windows with wmutils and publishes EWMH hints when X is reachable.
+pub struct X11Backend {
+    ewmh: Option<Arc<Ewmh>>,
+}
+
+impl X11Backend {
+    pub fn new(ewmh: Option<Arc<Ewmh>>) -> X11Backend {
+        X11Backend { ewmh }
+    }
+}
+
+impl Backend for X11Backend {
+    fn start(&mut self, state: &WMState) {
+        if let Some(ewmh) = &self.ewmh {
+            publish_ewmh(ewmh, state);
+        }
+    }
+
+    fn apply(&mut self, ops: &[Op], mutations: &[Mutation], state: &WMState, config: &Config) {
+        match &self.ewmh {
+            Some(ewmh) if !ops.is_empty() => {
+                ewmh.begin_layout();
+                run_ops(ops, config);
+                ewmh.end_layout();
+            }
+            _ => run_ops(ops, config),
+        }
+        if let Some(ewmh) = &self.ewmh {
+            actualize_ewmh(ewmh, mutations, state);
+        }
+    }
+
+    fn is_ignored(&self, window_id: WindowId) -> bool {
+        is_ignored(window_id)
+    }
+
+    fn window_struts(&self, window_id: WindowId) -> Option<Struts> {
+        self.ewmh.as_ref()?.window_struts(window_id)
+    }
+
+    fn is_urgent(&self, window_id: WindowId) -> bool {
+        self.ewmh
+            .as_ref()
+            .is_some_and(|ewmh| ewmh.is_urgent(window_id))
+    }
+
+    fn window_class(&self, window_id: WindowId) -> Option<(String, String)> {
+        self.ewmh.as_ref()?.window_class(window_id)
+    }
+
+    fn spawn(&mut self, command: &str) {
+        spawn(command);
+    }
+
+    fn close_window(&mut self, window_id: WindowId, config: &Config) {
+        match &self.ewmh {
+            Some(ewmh) => {
+                ewmh.close_window(window_id, Duration::from_millis(config.kill_timeout_ms))
+            }
+            None => kill_window(window_id),
+        }
+    }
+}
+
+/// Touches no windows and knows nothing about them, every window gets managed
+/// and the state still changes as if it did.
+#[derive(Debug, Default)]
+pub struct HeadlessBackend;
+
+impl Backend for HeadlessBackend {
+    fn apply(&mut self, _ops: &[Op], _mutations: &[Mutation], _state: &WMState, _config: &Config) {}
+}
+
+/// What processing an event did.
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// Changes to the state, in the order they were made
+    pub mutations: Vec<Mutation>,
+    /// What the backend was asked to do for them
+    pub ops: Vec<Op>,
+    /// Answer to a query event
+    pub reply: Option<String>,
+}
+
+/// Why an event was dropped, the state stays as it was before it.
+#[derive(Debug)]
+pub enum ProcessError {
+    Invalid(String),
+    Handler(HandlerError),
+    Panic(String),
+    Invariant(InvariantError),
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProcessError::Invalid(message) => f.write_str(message),
+            ProcessError::Handler(err) => err.fmt(f),
+            ProcessError::Panic(message) => write!(f, "handler panicked: {}", message),
+            ProcessError::Invariant(err) => write!(f, "event breaks the state: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+impl From<HandlerError> for ProcessError {
+    fn from(err: HandlerError) -> ProcessError {
+        ProcessError::Handler(err)
+    }
+}
+
+type PreHook = Box<dyn FnMut(&Event, &WMState)>;
+type PostHook = Box<dyn FnMut(&Event, &WMState, &Changes)>;
+
+/// Runs events through the handlers and applies the resulting state to the
+/// backend, so stdin, X, tests and anything else share one pipeline.
+pub struct EventProcessor<B: Backend> {
+    state: WMState,
+    config: Config,
+    backend: B,
+    pre_hooks: Vec<PreHook>,
+    post_hooks: Vec<PostHook>,
+}
+
+impl<B: Backend> EventProcessor<B> {
+    pub fn new(config: Config, mut backend: B) -> EventProcessor<B> {
+        let state = WMState::new(config.workspaces);
+        backend.start(&state);
+        EventProcessor {
+            state,
+            config,
+            backend,
+            pre_hooks: Vec::new(),
+            post_hooks: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> &WMState {
+        &self.state
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Runs `hook` before every event with the state it will be applied to.
+    pub fn add_pre_hook(&mut self, hook: impl FnMut(&Event, &WMState) + 'static) {
+        self.pre_hooks.push(Box::new(hook));
+    }
+
+    /// Runs `hook` after every successfully processed event with the resulting state.
+    pub fn add_post_hook(&mut self, hook: impl FnMut(&Event, &WMState, &Changes) + 'static) {
+        self.post_hooks.push(Box::new(hook));
+    }
+
+    pub fn process(&mut self, event: Event) -> Result<Changes, ProcessError> {
+        for hook in &mut self.pre_hooks {
+            hook(&event, &self.state);
+        }
+        let changes = self.dispatch(&event)?;
+        for hook in &mut self.post_hooks {
+            hook(&event, &self.state, &changes);
+        }
+        Ok(changes)
+    }
+
+    /// Swaps in a new config and lays the screen out again, the workspace count can't change.
+    pub fn reconfigure(&mut self, config: Config) -> Result<Changes, ProcessError> {
+        if config.workspaces != self.state.workspaces.len() {
+            return Err(ProcessError::Invalid(format!(
+                "can't go from {} to {} workspaces while running",
+                self.state.workspaces.len(),
+                config.workspaces
+            )));
+        }
+        self.config = config;
+        let mut now = Transaction::new(&mut self.state);
+        now.apply(Mutation::Reconfigure);
+        Ok(finish(now, &mut self.backend, &self.config))
+    }
+
+    fn dispatch(&mut self, event: &Event) -> Result<Changes, ProcessError> {
+        match event {
+            Event::Query(query) => {
+                return Ok(Changes {
+                    reply: Some(handle_query_event(&self.state, query.clone())),
+                    ..Changes::default()
+                })
+            }
+            Event::Invalid(message) => return Err(ProcessError::Invalid(message.clone())),
+            Event::Unknown => return Ok(Changes::default()),
+            _ => {}
+        }
+
+        // Failing or panicking handlers only lose their event, the state is rolled back
+        let config = &self.config;
+        let backend = &mut self.backend;
+        let mut now = Transaction::new(&mut self.state);
+        let handled = panic::catch_unwind(AssertUnwindSafe(|| match event.clone() {
+            Event::Window(event) => handle_window_event(&mut now, event, backend, config),
+            Event::Workspace(event) => handle_workspace_event(&mut now, event, config),
+            Event::Scratchpad(event) => handle_scratchpad_event(&mut now, event, backend, config),
+            Event::Client(event) => handle_client_event(&mut now, event, backend, config),
+            Event::Command(command) => handle_window_command(&mut now, command, backend, config),
+            Event::Pointer(event) => handle_pointer_event(&mut now, event, config),
+            Event::Query(_) | Event::Invalid(_) | Event::Unknown => Ok(false),
+        }));
+        match handled {
+            Ok(Ok(true)) => end_stale_drag(&mut now),
+            Ok(Ok(false)) => {
+                now.rollback();
+                return Ok(Changes::default());
+            }
+            Ok(Err(err)) => {
+                now.rollback();
+                return Err(err.into());
+            }
+            Err(payload) => {
+                now.rollback();
+                return Err(ProcessError::Panic(panic_message(payload)));
+            }
+        }
+        if let Err(err) = now.check_invariants() {
+            now.rollback();
+            return Err(ProcessError::Invariant(err));
+        }
+
+        Ok(finish(now, &mut self.backend, &self.config))
+    }
+}
+
+/// Works out the X operations for the transaction, commits it with the
+/// resulting focus and hands both to the backend.
+fn finish(mut now: Transaction, backend: &mut impl Backend, config: &Config) -> Changes {
+    if now.mutations().is_empty() {
+        return Changes::default();
+    }
+    let ops = reconcile(now.mutations(), now.before(), &now, config);
+    match focused_window(&ops) {
+        Some(target) if now.focused_window != Some(target) => {
+            now.apply(Mutation::FocusWindow(Some(target)));
+        }
+        // Switching to an empty workspace unmaps whatever had focus
+        None if now.focused_workspace != now.before().focused_workspace
+            && now.focus_target().is_none()
+            && now.focused_window.is_some() =>
+        {
+            now.apply(Mutation::FocusWindow(None));
+        }
+        _ => {}
+    }
+    backend.apply(&ops, now.mutations(), &now, config);
+    Changes {
+        mutations: now.commit(),
+        ops,
+        reply: None,
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown cause".to_string()
+    }
+}
@@ -1,15 +1,75 @@
-use ::derpywm::{
+use crate::ewmh::{Ewmh, ALL_DESKTOPS};
+use crate::geometry::{Anchor, Length, Rect, Struts};
+use crate::mutation::{Before, Mutation};
+use crate::{
     border_window, focus_window, foreground_window, fullscreen_window, map_window, move_window,
-    tile_windows, unmap_window, Config,
+    unmap_window, weighted_tile_layout, Config, DEFAULT_COLUMN_SHARE, DEFAULT_WEIGHT,
 };
 
+use std::fmt;
+
 pub type ScratchpadName = String;
-pub type WindowId = String;
+pub use crate::WindowId;
 
 #[derive(Debug, Clone)]
 pub struct ScratchpadState {
     pub windows: Vec<(ScratchpadName, WindowId)>,
-    pub shown: Option<ScratchpadName>,
+    /// Shown scratchpads from bottom to top, the top one has focus
+    pub shown: Vec<ScratchpadName>,
+    /// Launched from config, waiting for their window to map
+    pub pending: Vec<ScratchpadName>,
+    /// Geometry changed with `SP_RESIZE`, overrides the config
+    pub geometry: Vec<(ScratchpadName, ScratchpadGeometry)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScratchpadGeometry {
+    pub size: (Length, Length),
+    pub anchor: Anchor,
+}
+
+impl ScratchpadState {
+    pub fn window(&self, name: &str) -> Option<WindowId> {
+        self.windows
+            .iter()
+            .find(|(sname, _)| sname == name)
+            .map(|(_, wid)| *wid)
+    }
+
+    pub fn name(&self, window_id: WindowId) -> Option<&ScratchpadName> {
+        self.windows
+            .iter()
+            .find(|(_, wid)| *wid == window_id)
+            .map(|(name, _)| name)
+    }
+
+    /// Windows of shown scratchpads from bottom to top.
+    pub fn shown_windows(&self) -> Vec<WindowId> {
+        self.shown
+            .iter()
+            .filter_map(|name| self.window(name))
+            .collect()
+    }
+
+    /// Window of the topmost shown scratchpad.
+    pub fn top_window(&self) -> Option<WindowId> {
+        self.shown.last().and_then(|name| self.window(name))
+    }
+
+    /// Where the scratchpad is shown: resized at runtime, configured or the global default.
+    pub fn geometry(&self, name: &str, config: &Config) -> ScratchpadGeometry {
+        if let Some((_, geometry)) = self.geometry.iter().find(|(sname, _)| sname == name) {
+            return *geometry;
+        }
+        let scratchpad = config.scratchpad(name);
+        let (w, h) = config.scratchpad_size;
+        ScratchpadGeometry {
+            size: scratchpad
+                .and_then(|scratchpad| scratchpad.size)
+                .unwrap_or((Length::Pixels(w), Length::Pixels(h))),
+            anchor: scratchpad.map_or(Anchor::Center, |scratchpad| scratchpad.anchor),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -17,6 +77,80 @@ pub struct WorkspaceState {
     pub windows: Vec<WindowId>,
     pub focus_history: Vec<WindowId>,
     pub fullscreen: Option<WindowId>,
+    /// Geometry of `windows` that aren't tiled
+    pub floating: Vec<(WindowId, Rect)>,
+    /// Row weights of resized tiled windows, others have `DEFAULT_WEIGHT`
+    pub weights: Vec<(WindowId, u32)>,
+    /// Thousandths of the width taken by the left column
+    pub column_share: u32,
+}
+
+impl WorkspaceState {
+    pub fn tiled_windows(&self) -> Vec<WindowId> {
+        self.windows
+            .iter()
+            .filter(|wid| self.floating_rect(**wid).is_none())
+            .copied()
+            .collect()
+    }
+
+    /// Tiled windows with their row weights, in layout order.
+    pub fn tiled_weights(&self) -> Vec<(WindowId, u32)> {
+        self.tiled_windows()
+            .into_iter()
+            .map(|wid| {
+                let weight = self.weight(wid);
+                (wid, weight)
+            })
+            .collect()
+    }
+
+    pub fn weight(&self, window_id: WindowId) -> u32 {
+        self.weights
+            .iter()
+            .find(|(wid, _)| *wid == window_id)
+            .map_or(DEFAULT_WEIGHT, |(_, weight)| *weight)
+    }
+
+    pub fn set_weight(&mut self, window_id: WindowId, weight: u32) {
+        self.weights.retain(|(wid, _)| *wid != window_id);
+        if weight != DEFAULT_WEIGHT {
+            self.weights.push((window_id, weight));
+        }
+    }
+
+    pub fn floating_rect(&self, window_id: WindowId) -> Option<Rect> {
+        self.floating
+            .iter()
+            .find(|(wid, _)| *wid == window_id)
+            .map(|(_, rect)| *rect)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DragKind {
+    Move,
+    Resize,
+}
+
+/// Pointer drag in progress, `rect` is the window geometry when it started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drag {
+    pub window_id: WindowId,
+    pub kind: DragKind,
+    pub origin: (i32, i32),
+    pub rect: Rect,
+}
+
+impl Drag {
+    /// Window geometry with the pointer at `(x, y)`.
+    pub fn apply(&self, (x, y): (i32, i32)) -> Rect {
+        let (dx, dy) = (x - self.origin.0, y - self.origin.1);
+        match self.kind {
+            DragKind::Move => self.rect.translate(dx, dy),
+            DragKind::Resize => self.rect.grow(dx, dy),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +159,13 @@ pub struct WMState {
     pub scratchpad: ScratchpadState,
     pub focused_workspace: usize,
     pub focused_window: Option<WindowId>,
+    pub dock_struts: Vec<(WindowId, Struts)>,
+    pub urgent: Vec<WindowId>,
+    pub drag: Option<Drag>,
+    /// Windows shown on every workspace with their geometry
+    pub sticky: Vec<(WindowId, Rect)>,
+    /// Windows created but not mapped yet, only their first map manages them
+    pub created: Vec<WindowId>,
 }
 
 impl WMState {
@@ -34,213 +175,646 @@ impl WMState {
                 WorkspaceState {
                     windows: Vec::new(),
                     focus_history: Vec::new(),
-                    fullscreen: None
+                    fullscreen: None,
+                    floating: Vec::new(),
+                    weights: Vec::new(),
+                    column_share: DEFAULT_COLUMN_SHARE,
                 };
                 workspace_count
             ],
             scratchpad: ScratchpadState {
                 windows: Vec::new(),
-                shown: None,
+                shown: Vec::new(),
+                pending: Vec::new(),
+                geometry: Vec::new(),
             },
             focused_workspace: 0,
             focused_window: None,
+            dock_struts: Vec::new(),
+            urgent: Vec::new(),
+            drag: None,
+            sticky: Vec::new(),
+            created: Vec::new(),
         }
     }
-}
 
-pub fn actualize_screen(before: &WMState, now: &WMState, config: &Config) -> Option<WindowId> {
-    let focused_workspace_before = &before.workspaces[now.focused_workspace];
-    let focused_workspace = &now.workspaces[now.focused_workspace];
-    // Focus workspace if focused_workspace changed
-    if before.focused_workspace != now.focused_workspace {
-        before.workspaces[before.focused_workspace]
-            .windows
+    /// Window that should have focus: the topmost shown scratchpad, then the focused
+    /// workspace's fullscreen window, a focused sticky window, then the workspace's
+    /// most recently focused one.
+    pub fn focus_target(&self) -> Option<WindowId> {
+        let workspace = &self.workspaces[self.focused_workspace];
+        self.scratchpad
+            .top_window()
+            .or(workspace.fullscreen)
+            .or_else(|| {
+                self.focused_window
+                    .filter(|wid| self.sticky_rect(*wid).is_some())
+            })
+            .or_else(|| workspace.focus_history.last().copied())
+    }
+
+    /// Whether the window is on a workspace, a scratchpad or sticky.
+    pub fn manages(&self, window_id: WindowId) -> bool {
+        self.workspaces
             .iter()
-            .for_each(unmap_window);
-        focused_workspace.windows.iter().for_each(map_window);
-    }
-
-    // Show scratchpad
-    if before.scratchpad.shown != now.scratchpad.shown {
-        if before.scratchpad.shown.is_none() {
-            show_scratchpad(
-                &now.scratchpad,
-                config.workspace_size,
-                config.scratchpad_size,
-                config.focused_border.as_str(),
-            );
-        } else {
-            // Need to hide shown before window
-            let shown_before_wid = scratchpad_shown_wid(&before.scratchpad).unwrap();
-            unmap_window(shown_before_wid);
-
-            // Show new window if needed
-            show_scratchpad(
-                &now.scratchpad,
-                config.workspace_size,
-                config.scratchpad_size,
-                config.focused_border.as_str(),
+            .any(|workspace| workspace.windows.contains(&window_id))
+            || self.scratchpad.name(window_id).is_some()
+            || self.sticky_rect(window_id).is_some()
+    }
+
+    pub fn sticky_rect(&self, window_id: WindowId) -> Option<Rect> {
+        self.sticky
+            .iter()
+            .find(|(wid, _)| *wid == window_id)
+            .map(|(_, rect)| *rect)
+    }
+
+    /// Screen area left for windows after panels and docks reserved their edges.
+    pub fn usable_area(&self, config: &Config) -> Rect {
+        let struts = self
+            .dock_struts
+            .iter()
+            .fold(config.reserved, |acc, (_, struts)| acc.max(*struts));
+        Rect::from_size(config.workspace_size).reserve(struts)
+    }
+
+    /// Where the focused workspace's tiled windows go.
+    pub fn tiled_rects(&self, config: &Config) -> Vec<(WindowId, Rect)> {
+        let workspace = &self.workspaces[self.focused_workspace];
+        let tiled = workspace.tiled_weights();
+        let weights: Vec<u32> = tiled.iter().map(|(_, weight)| *weight).collect();
+        let rects = weighted_tile_layout(
+            &weights,
+            workspace.column_share,
+            config.gaps,
+            self.usable_area(config),
+        );
+        tiled.into_iter().map(|(wid, _)| wid).zip(rects).collect()
+    }
+
+    pub fn fullscreen_windows(&self) -> Vec<WindowId> {
+        self.workspaces
+            .iter()
+            .filter_map(|workspace| workspace.fullscreen)
+            .collect()
+    }
+
+    /// Every managed window with its EWMH desktop, scratchpads and sticky windows
+    /// are on all of them.
+    pub fn window_desktops(&self) -> Vec<(WindowId, u32)> {
+        let mut windows: Vec<(WindowId, u32)> = Vec::new();
+        for (i, workspace) in self.workspaces.iter().enumerate() {
+            windows.extend(workspace.windows.iter().map(|wid| (*wid, i as u32)));
+        }
+        windows.extend(
+            self.scratchpad
+                .windows
+                .iter()
+                .map(|(_, wid)| (*wid, ALL_DESKTOPS)),
+        );
+        windows.extend(self.sticky.iter().map(|(wid, _)| (*wid, ALL_DESKTOPS)));
+        windows
+    }
+}
+
+/// Inconsistency found by `WMState::check_invariants`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvariantError(pub String);
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvariantError {}
+
+fn check(ok: bool, message: impl FnOnce() -> String) -> Result<(), InvariantError> {
+    if ok {
+        Ok(())
+    } else {
+        Err(InvariantError(message()))
+    }
+}
+
+impl WMState {
+    /// Validates that every window is managed in exactly one place and that
+    /// everything referring to windows or scratchpads points at existing ones.
+    pub fn check_invariants(&self) -> Result<(), InvariantError> {
+        check(self.focused_workspace < self.workspaces.len(), || {
+            format!("focused workspace {} doesn't exist", self.focused_workspace)
+        })?;
+
+        let mut places: Vec<(WindowId, String)> = Vec::new();
+        for (i, workspace) in self.workspaces.iter().enumerate() {
+            places.extend(
+                workspace
+                    .windows
+                    .iter()
+                    .map(|wid| (*wid, format!("workspace {}", i + 1))),
             );
         }
+        places.extend(
+            self.scratchpad
+                .windows
+                .iter()
+                .map(|(name, wid)| (*wid, format!("scratchpad {}", name))),
+        );
+        places.extend(
+            self.sticky
+                .iter()
+                .map(|(wid, _)| (*wid, "sticky".to_string())),
+        );
+        for (i, (wid, place)) in places.iter().enumerate() {
+            if let Some((_, other)) = places[..i].iter().find(|(other, _)| other == wid) {
+                return Err(InvariantError(format!(
+                    "{} is both in {} and {}",
+                    wid, other, place
+                )));
+            }
+        }
+        let managed = |wid: WindowId| places.iter().any(|(managed, _)| *managed == wid);
+
+        for (i, workspace) in self.workspaces.iter().enumerate() {
+            for (n, wid) in workspace.focus_history.iter().enumerate() {
+                check(workspace.windows.contains(wid), || {
+                    format!("focus history of workspace {} has foreign {}", i + 1, wid)
+                })?;
+                check(!workspace.focus_history[..n].contains(wid), || {
+                    format!("focus history of workspace {} repeats {}", i + 1, wid)
+                })?;
+            }
+            if let Some(wid) = workspace.fullscreen {
+                check(
+                    workspace.windows.contains(&wid) || self.scratchpad.name(wid).is_some(),
+                    || format!("fullscreen {} of workspace {} is gone", wid, i + 1),
+                )?;
+            }
+            for (wid, _) in &workspace.floating {
+                check(workspace.windows.contains(wid), || {
+                    format!("floating {} isn't on workspace {}", wid, i + 1)
+                })?;
+            }
+            for (wid, _) in &workspace.weights {
+                check(workspace.windows.contains(wid), || {
+                    format!("weighted {} isn't on workspace {}", wid, i + 1)
+                })?;
+            }
+            check(workspace.column_share <= 1000, || {
+                format!("column share of workspace {} is over 1000", i + 1)
+            })?;
+        }
+
+        for (n, name) in self.scratchpad.shown.iter().enumerate() {
+            check(self.scratchpad.window(name).is_some(), || {
+                format!("shown scratchpad {} has no window", name)
+            })?;
+            check(!self.scratchpad.shown[..n].contains(name), || {
+                format!("scratchpad {} is shown twice", name)
+            })?;
+        }
+        for (n, (name, _)) in self.scratchpad.windows.iter().enumerate() {
+            check(
+                !self.scratchpad.windows[..n]
+                    .iter()
+                    .any(|(other, _)| other == name),
+                || format!("scratchpad {} has two windows", name),
+            )?;
+            check(!self.scratchpad.pending.contains(name), || {
+                format!("scratchpad {} is both running and pending", name)
+            })?;
+        }
+
+        for wid in &self.urgent {
+            check(managed(*wid), || format!("urgent {} isn't managed", wid))?;
+        }
+        if let Some(drag) = &self.drag {
+            check(managed(drag.window_id), || {
+                format!("dragged {} isn't managed", drag.window_id)
+            })?;
+        }
+        for wid in &self.created {
+            check(!managed(*wid), || {
+                format!("created {} is already managed", wid)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Publishes every EWMH property, for when the window manager starts.
+pub fn publish_ewmh(ewmh: &Ewmh, now: &WMState) {
+    ewmh.set_number_of_desktops(now.workspaces.len());
+    let names: Vec<String> = (1..=now.workspaces.len()).map(|i| i.to_string()).collect();
+    ewmh.set_desktop_names(&names);
+    ewmh.set_current_desktop(now.focused_workspace);
+    ewmh.set_active_window(now.focused_window);
+    for wid in now.fullscreen_windows() {
+        ewmh.set_fullscreen(wid, true);
+    }
+    for wid in &now.urgent {
+        ewmh.set_demands_attention(*wid, true);
+    }
+    for (wid, _) in &now.dock_struts {
+        ewmh.watch_dock(*wid);
     }
+    let desktops = now.window_desktops();
+    ewmh.set_client_list(desktops.iter().map(|(wid, _)| *wid));
+    for (wid, desktop) in desktops {
+        ewmh.set_window_desktop(wid, desktop);
+        ewmh.watch_window(wid);
+    }
+}
 
-    // Unmap moved from workspace window
-    for window in &focused_workspace_before.windows {
-        if !focused_workspace.windows.contains(window) {
-            unmap_window(window);
+/// Publishes the EWMH properties that `mutations` changed.
+pub fn actualize_ewmh(ewmh: &Ewmh, mutations: &[Mutation], now: &WMState) {
+    let mut clients_changed = false;
+    let mut desktops_changed = false;
+    let mut focus_changed = false;
+    for mutation in mutations {
+        match mutation {
+            Mutation::FocusWorkspace { .. } => desktops_changed = true,
+            Mutation::FocusWindow(_) => focus_changed = true,
+            Mutation::Fullscreen { from, to, .. } => {
+                if let Some(from) = from {
+                    ewmh.set_fullscreen(*from, false);
+                }
+                if let Some(to) = to {
+                    ewmh.set_fullscreen(*to, true);
+                }
+            }
+            Mutation::Urgent { window, urgent } => ewmh.set_demands_attention(*window, *urgent),
+            Mutation::DockStruts {
+                window,
+                struts: Some(_),
+            } => ewmh.watch_dock(*window),
+            Mutation::AddWindow { workspace, window } => {
+                clients_changed = true;
+                ewmh.set_window_desktop(*window, *workspace as u32);
+                ewmh.watch_window(*window);
+            }
+            Mutation::AddScratchpad { window, .. }
+            | Mutation::Sticky {
+                window,
+                rect: Some(_),
+            } => {
+                clients_changed = true;
+                ewmh.set_window_desktop(*window, ALL_DESKTOPS);
+                ewmh.watch_window(*window);
+            }
+            Mutation::RemoveWindow { .. }
+            | Mutation::RemoveScratchpad { .. }
+            | Mutation::Sticky { rect: None, .. } => clients_changed = true,
+            _ => {}
         }
     }
-    // Map moved to workspace window
-    for window in &focused_workspace.windows {
-        if !focused_workspace_before.windows.contains(window) {
-            map_window(window);
+    if desktops_changed {
+        ewmh.set_current_desktop(now.focused_workspace);
+    }
+    if focus_changed {
+        ewmh.set_active_window(now.focused_window);
+    }
+    if clients_changed {
+        ewmh.set_client_list(now.window_desktops().iter().map(|(wid, _)| *wid));
+    }
+}
+
+/// Window to focus after going from `before` to `now`, `None` keeps the current focus.
+pub fn refocus(before: &Before, now: &WMState) -> Option<WindowId> {
+    let target = now.focus_target()?;
+    // Overlays only take focus when they change, so other windows can still be clicked
+    let overlay = now.scratchpad.top_window().is_some()
+        || now.workspaces[now.focused_workspace].fullscreen.is_some();
+    if overlay
+        && before.focused_workspace == now.focused_workspace
+        && before.focus_target == Some(target)
+    {
+        return None;
+    }
+    Some(target)
+}
+
+/// X operation asked for by the reconciler.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Map(WindowId),
+    Unmap(WindowId),
+    Border(WindowId, Border),
+    Move(WindowId, Rect),
+    /// Drops the border and covers the whole screen
+    Fullscreen(WindowId, Rect),
+    Raise(WindowId),
+    Focus(WindowId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Border {
+    Focused,
+    Unfocused,
+    Urgent,
+}
+
+impl Op {
+    fn window(&self) -> WindowId {
+        match self {
+            Op::Map(wid)
+            | Op::Unmap(wid)
+            | Op::Border(wid, _)
+            | Op::Move(wid, _)
+            | Op::Fullscreen(wid, _)
+            | Op::Raise(wid)
+            | Op::Focus(wid) => *wid,
         }
     }
 
-    // Add border if window added to focused workspace
-    if focused_workspace_before.windows.len() < focused_workspace.windows.len() {
-        border_window(
-            focused_workspace.windows.iter().last().unwrap(),
-            config.unfocused_border.as_str(),
-        );
+    /// Whether doing this makes an earlier `other` pointless.
+    fn supersedes(&self, other: &Op) -> bool {
+        match (self, other) {
+            (Op::Focus(_), Op::Focus(_)) => true,
+            _ if self.window() != other.window() => false,
+            (Op::Map(_), Op::Map(_) | Op::Unmap(_))
+            | (Op::Unmap(_), Op::Map(_) | Op::Unmap(_))
+            | (Op::Border(..), Op::Border(..))
+            | (Op::Move(..), Op::Move(..))
+            | (Op::Fullscreen(..), Op::Move(..) | Op::Fullscreen(..))
+            | (Op::Raise(_), Op::Raise(_)) => true,
+            _ => false,
+        }
     }
+}
 
-    // Add border if window got unfullscreened
-    if focused_workspace_before.fullscreen.is_some() && focused_workspace.fullscreen.is_none() {
-        let wid = focused_workspace_before.fullscreen.as_ref().unwrap();
-        border_window(wid.as_str(), config.unfocused_border.as_str());
+/// Operations where each one only stays if no later one overrides it.
+#[derive(Default)]
+struct Ops(Vec<Op>);
+
+impl Ops {
+    fn push(&mut self, op: Op) {
+        self.0.retain(|old| !op.supersedes(old));
+        self.0.push(op);
     }
+}
 
-    // Tile windows if focused workspace windows changed
-    // or window got unfullscreened
-    // or scratchpad windows changed
-    if (before.workspaces[before.focused_workspace].windows != focused_workspace.windows)
-        || (focused_workspace_before.fullscreen.is_some() && focused_workspace.fullscreen.is_none())
-        || (now.scratchpad.windows != before.scratchpad.windows)
+/// Window the operations leave focused, if they move focus at all.
+pub fn focused_window(ops: &[Op]) -> Option<WindowId> {
+    ops.iter().rev().find_map(|op| match op {
+        Op::Focus(wid) => Some(*wid),
+        _ => None,
+    })
+}
+
+/// Whether the window is on screen: on the focused workspace, sticky or a shown scratchpad.
+fn is_visible(now: &WMState, window_id: WindowId) -> bool {
+    now.workspaces[now.focused_workspace]
+        .windows
+        .contains(&window_id)
+        || now.sticky_rect(window_id).is_some()
+        || now.scratchpad.shown_windows().contains(&window_id)
+}
+
+/// X operations that bring the screen from `before` to `now` after `mutations`.
+pub fn reconcile(
+    mutations: &[Mutation],
+    before: &Before,
+    now: &WMState,
+    config: &Config,
+) -> Vec<Op> {
+    // Windows waiting for their first map aren't on screen yet
+    if mutations
+        .iter()
+        .all(|mutation| matches!(mutation, Mutation::Created { .. }))
     {
-        tile_windows(
-            focused_workspace.windows.clone(),
-            config.gaps,
-            config.workspace_size,
-            config.panel_width,
-        );
+        return Vec::new();
     }
+    let mut ops = Ops::default();
+    let ws = now.focused_workspace;
+    let workspace = &now.workspaces[ws];
+    let switched = before.focused_workspace != ws;
+    let mut reconfigured = false;
+    let mut area_changed = false;
+    let mut retile = false;
+    let mut restack = false;
+    let mut fullscreened = false;
+    // Windows that may have appeared or disappeared
+    let mut shown_or_hidden: Vec<WindowId> = Vec::new();
+    let mut unfullscreened: Vec<WindowId> = Vec::new();
+    let mut floated: Vec<WindowId> = Vec::new();
+    let mut moved_scratchpads: Vec<&str> = Vec::new();
 
-    // Show scratchpad if scratchpad window was unfullscreened
-    if let Some(fullscreen) = &focused_workspace_before.fullscreen {
-        if let Some(scratchpad_wid) = scratchpad_shown_wid(&now.scratchpad) {
-            if scratchpad_wid == fullscreen.as_str() && focused_workspace.fullscreen.is_none() {
-                show_scratchpad(
-                    &now.scratchpad,
-                    config.workspace_size,
-                    config.scratchpad_size,
-                    config.focused_border.as_str(),
-                );
+    if switched {
+        shown_or_hidden.extend(&now.workspaces[before.focused_workspace].windows);
+        shown_or_hidden.extend(&workspace.windows);
+        retile = true;
+        restack = true;
+    }
+    for mutation in mutations {
+        match mutation {
+            Mutation::AddWindow { workspace, window } => {
+                shown_or_hidden.push(*window);
+                if *workspace == ws {
+                    ops.push(Op::Border(*window, Border::Unfocused));
+                    retile = true;
+                    restack = true;
+                }
+            }
+            Mutation::RemoveWindow { workspace, window } => {
+                shown_or_hidden.push(*window);
+                if *workspace == ws {
+                    retile = true;
+                    restack = true;
+                }
+            }
+            Mutation::OrderWindows { workspace, .. }
+            | Mutation::Weight { workspace, .. }
+            | Mutation::ColumnShare { workspace, .. } => retile |= *workspace == ws,
+            Mutation::Fullscreen {
+                workspace,
+                from,
+                to,
+            } if *workspace == ws => {
+                unfullscreened.extend(from.filter(|from| Some(*from) != *to));
+                // Unfullscreened windows go back to their tile
+                retile |= from.is_some();
+                fullscreened |= to.is_some();
+                restack = true;
+            }
+            Mutation::Float {
+                workspace,
+                window,
+                from,
+                to,
+            } if *workspace == ws => {
+                floated.push(*window);
+                retile |= from.is_none() || to.is_none();
+                restack = true;
+            }
+            Mutation::AddScratchpad { window, .. } | Mutation::RemoveScratchpad { window, .. } => {
+                shown_or_hidden.push(*window);
+                restack = true;
+            }
+            Mutation::ShowScratchpad(name) => {
+                moved_scratchpads.push(name);
+                restack = true;
             }
+            Mutation::HideScratchpad(name) => {
+                shown_or_hidden.extend(now.scratchpad.window(name));
+                restack = true;
+            }
+            Mutation::ScratchpadGeometry { name, .. } => moved_scratchpads.push(name),
+            Mutation::Sticky { window, rect } => {
+                shown_or_hidden.push(*window);
+                if let Some(rect) = rect {
+                    ops.push(Op::Move(*window, *rect));
+                }
+                restack = true;
+            }
+            Mutation::DockStruts { .. } => area_changed = true,
+            Mutation::Urgent { window, urgent } => {
+                if *urgent {
+                    ops.push(Op::Border(*window, Border::Urgent));
+                } else if now.focused_window != Some(*window) {
+                    // Focused windows are never urgent
+                    ops.push(Op::Border(*window, Border::Unfocused));
+                }
+            }
+            Mutation::Reconfigure => reconfigured = true,
+            _ => {}
         }
     }
+    let area_changed = area_changed || reconfigured;
 
-    // Show fullscreen window
-    if focused_workspace_before.fullscreen != focused_workspace.fullscreen
-        || before.focused_workspace != now.focused_workspace
-    {
-        if let Some(fullscreen) = &focused_workspace.fullscreen {
-            fullscreen_window(fullscreen, config.workspace_size);
-            foreground_window(fullscreen);
+    // Borders changed while the workspace was hidden, like a window unfullscreened
+    // through `_NET_WM_STATE`, are put right once it is shown again
+    if switched {
+        for wid in &workspace.windows {
+            if workspace.fullscreen != Some(*wid) {
+                let border = if now.urgent.contains(wid) {
+                    Border::Urgent
+                } else {
+                    Border::Unfocused
+                };
+                ops.push(Op::Border(*wid, border));
+            }
         }
     }
 
-    // Actualize focus
-    // Focus window if it became fullscreen or on workspace focus change
-    if (focused_workspace_before.fullscreen.is_none() && focused_workspace.fullscreen.is_some())
-        || (before.focused_workspace != now.focused_workspace
-            && focused_workspace.fullscreen.is_some())
-    {
-        let fullscreen = focused_workspace.fullscreen.as_ref().unwrap();
-        // Unfocus previous window
-        if let Some(focused_window) = &now.focused_window {
-            border_window(focused_window, config.unfocused_border.as_str());
-        }
-        // Focus new window
-        focus_window(fullscreen);
-        return Some(fullscreen.to_string());
-    }
-    // No need to refocus fullscreen windows
-    if focused_workspace.fullscreen.is_some() {
-        return now.focused_window.clone();
-    }
-    // Focus scratchpad window if shown
-    if (before.scratchpad.shown != now.scratchpad.shown
-        || before.focused_workspace != now.focused_workspace)
-        && now.scratchpad.shown.is_some()
-    {
-        let shown_wid = scratchpad_shown_wid(&now.scratchpad).unwrap();
-        // Unfocus previous window
-        if let Some(focused_window) = &now.focused_window {
-            border_window(focused_window, config.unfocused_border.as_str());
-        }
-        // Focus new window
-        border_window(shown_wid, config.focused_border.as_str());
-        focus_window(shown_wid);
-        return Some(shown_wid.to_string());
-    }
-    // No need to refocus scratchpad windows
-    if now.scratchpad.shown.is_some() {
-        return now.focused_window.clone();
-    }
-    // Focus last window in focus history
-    if let Some(wid) = focused_workspace.focus_history.iter().last() {
-        // Unfocus previous window
-        if let Some(focused_window) = &now.focused_window {
-            border_window(focused_window, config.unfocused_border.as_str());
-        }
-        // Focus new window
-        border_window(wid, config.focused_border.as_str());
-        focus_window(wid);
-        return Some(wid.to_string());
-    }
-    now.focused_window.clone()
-}
-
-fn scratchpad_shown_wid(scratchpad: &ScratchpadState) -> Option<&str> {
-    if let Some(shown) = &scratchpad.shown {
-        Some(
-            scratchpad
-                .windows
-                .iter()
-                .find(|(name, _)| name == shown.as_str())
-                .unwrap()
-                .1
-                .as_str(),
-        )
-    } else {
-        None
-    }
-}
-fn show_scratchpad(
-    scratchpad: &ScratchpadState,
-    workspace_size: (usize, usize),
-    scratchpad_size: (usize, usize),
-    border: impl Into<String>,
-) {
-    if let Some(shown) = &scratchpad.shown {
-        let shown_wid = scratchpad
-            .windows
-            .iter()
-            .find(|(name, _)| name == shown.as_str())
-            .unwrap()
-            .1
-            .as_str();
-
-        map_window(shown_wid);
-        move_window(
-            shown_wid,
-            (workspace_size.0 - scratchpad_size.0) / 2,
-            (workspace_size.1 - scratchpad_size.1) / 2,
-            scratchpad_size.0,
-            scratchpad_size.1,
-        );
-        foreground_window(shown_wid);
-        border_window(shown_wid, border);
+    // Map what appeared and unmap what disappeared
+    for (i, wid) in shown_or_hidden.iter().enumerate() {
+        if shown_or_hidden[..i].contains(wid) {
+            continue;
+        }
+        if is_visible(now, *wid) {
+            ops.push(Op::Map(*wid));
+        } else {
+            ops.push(Op::Unmap(*wid));
+        }
+    }
+
+    for wid in &unfullscreened {
+        ops.push(Op::Border(*wid, Border::Unfocused));
+    }
+
+    // Place floating windows that were moved, resized or just got floating
+    for &(wid, rect) in &workspace.floating {
+        let moved =
+            switched || reconfigured || floated.contains(&wid) || unfullscreened.contains(&wid);
+        if moved && workspace.fullscreen != Some(wid) {
+            ops.push(Op::Move(wid, rect));
+            ops.push(Op::Raise(wid));
+        }
+    }
+
+    // Tile the workspace when its tiled windows, their sizes or the usable area
+    // changed, a fullscreen window keeps covering the screen
+    if retile || area_changed {
+        for (wid, rect) in now.tiled_rects(config) {
+            if workspace.fullscreen != Some(wid) {
+                ops.push(Op::Move(wid, rect));
+            }
+        }
+    }
+
+    if let Some(fullscreen) = workspace.fullscreen {
+        if switched || fullscreened || reconfigured {
+            ops.push(Op::Fullscreen(
+                fullscreen,
+                Rect::from_size(config.workspace_size),
+            ));
+            ops.push(Op::Raise(fullscreen));
+        }
+    }
+
+    // Scratchpads are overlays following the focused workspace, place them
+    // when they get shown, resized, unfullscreened or the usable area changes
+    let area = now.usable_area(config);
+    let top = now.scratchpad.top_window();
+    for name in &now.scratchpad.shown {
+        let wid = match now.scratchpad.window(name) {
+            Some(wid) => wid,
+            None => continue,
+        };
+        let moved = switched
+            || area_changed
+            || moved_scratchpads.contains(&name.as_str())
+            || unfullscreened.contains(&wid);
+        if moved && workspace.fullscreen != Some(wid) {
+            let geometry = now.scratchpad.geometry(name, config);
+            ops.push(Op::Map(wid));
+            ops.push(Op::Move(wid, area.anchored(geometry.size, geometry.anchor)));
+            ops.push(Op::Raise(wid));
+            let border = if top == Some(wid) {
+                Border::Focused
+            } else {
+                Border::Unfocused
+            };
+            ops.push(Op::Border(wid, border));
+        }
+    }
+    // and keep them above everything else in their stacking order,
+    // sticky windows go right below them unless there is a fullscreen window
+    if restack {
+        if workspace.fullscreen.is_none() {
+            for (wid, _) in &now.sticky {
+                ops.push(Op::Raise(*wid));
+            }
+        }
+        for wid in now.scratchpad.shown_windows() {
+            ops.push(Op::Raise(wid));
+        }
+    }
+
+    // Unfocus previous windows and focus the new one, fullscreen ones have no border
+    if let Some(target) = refocus(before, now) {
+        for wid in [before.focused_window, now.focused_window].iter().flatten() {
+            if *wid != target {
+                ops.push(Op::Border(*wid, Border::Unfocused));
+            }
+        }
+        if workspace.fullscreen != Some(target) {
+            ops.push(Op::Border(target, Border::Focused));
+        }
+        ops.push(Op::Focus(target));
+    }
+    ops.0
+}
+
+/// Carries out the operations with wmutils.
+pub fn run_ops(ops: &[Op], config: &Config) {
+    for op in ops {
+        match op {
+            Op::Map(wid) => map_window(*wid),
+            Op::Unmap(wid) => unmap_window(*wid),
+            Op::Border(wid, border) => {
+                let color = match border {
+                    Border::Focused => &config.focused_border,
+                    Border::Unfocused => &config.unfocused_border,
+                    Border::Urgent => &config.urgent_border,
+                };
+                border_window(*wid, color.as_str());
+            }
+            Op::Move(wid, rect) => move_window(*wid, *rect),
+            Op::Fullscreen(wid, rect) => fullscreen_window(*wid, *rect),
+            Op::Raise(wid) => foreground_window(*wid),
+            Op::Focus(wid) => focus_window(*wid),
+        }
     }
 }
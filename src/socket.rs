@@ -0,0 +1,56 @@
+use crate::{parse_event, Event};
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+/// Where the answer to an event goes, events from stdin and X have none.
+pub type Reply = Sender<String>;
+
+/// `$XDG_RUNTIME_DIR/derpy-wm.sock`, falling back to the temporary directory.
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("derpy-wm.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("derpy-wm-{}.sock", user))
+        }
+    }
+}
+
+/// Accepts clients on `path` in the background. Every line a client writes is
+/// sent as an event, and its answer is written back before the next line is
+/// read, so `echo QUERY_STATE | socat - UNIX-CONNECT:<path>` prints the state.
+pub fn listen(path: &Path, events: Sender<(Event, Option<Reply>)>) -> io::Result<()> {
+    // A socket nobody listens on is left over from a previous run
+    if path.exists() && UnixStream::connect(path).is_err() {
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let events = events.clone();
+            thread::spawn(move || serve(stream, events).ok());
+        }
+    });
+    Ok(())
+}
+
+fn serve(stream: UnixStream, events: Sender<(Event, Option<Reply>)>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let (reply, answer) = mpsc::channel();
+        if events.send((parse_event(line), Some(reply))).is_err() {
+            break;
+        }
+        match answer.recv() {
+            Ok(answer) if answer.is_empty() => {}
+            Ok(answer) => writeln!(writer, "{}", answer)?,
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+#![allow(dead_code)]
+
+use derpywm::geometry::Struts;
+use derpywm::mutation::Mutation;
+use derpywm::processor::{Backend, Changes, EventProcessor, ProcessError};
+use derpywm::reconciler::{Op, WMState};
+use derpywm::{parse_event, Config, Event, WindowId};
+
+/// Backend that knows about docks and window classes and records commands
+/// instead of running them.
+#[derive(Default)]
+pub struct FakeBackend {
+    pub docks: Vec<(WindowId, Struts)>,
+    pub classes: Vec<(WindowId, String)>,
+    pub urgent: Vec<WindowId>,
+    pub spawned: Vec<String>,
+    pub closed: Vec<WindowId>,
+}
+
+impl Backend for FakeBackend {
+    fn apply(&mut self, _: &[Op], _: &[Mutation], _: &WMState, _: &Config) {}
+
+    fn is_ignored(&self, window_id: WindowId) -> bool {
+        self.docks.iter().any(|(wid, _)| *wid == window_id)
+    }
+
+    fn window_struts(&self, window_id: WindowId) -> Option<Struts> {
+        self.docks
+            .iter()
+            .find(|(wid, _)| *wid == window_id)
+            .map(|(_, struts)| *struts)
+    }
+
+    fn is_urgent(&self, window_id: WindowId) -> bool {
+        self.urgent.contains(&window_id)
+    }
+
+    fn window_class(&self, window_id: WindowId) -> Option<(String, String)> {
+        self.classes
+            .iter()
+            .find(|(wid, _)| *wid == window_id)
+            .map(|(_, class)| (class.to_lowercase(), class.clone()))
+    }
+
+    fn spawn(&mut self, command: &str) {
+        self.spawned.push(command.to_string());
+    }
+
+    fn close_window(&mut self, window_id: WindowId, _: &Config) {
+        self.closed.push(window_id);
+    }
+}
+
+/// Drives the same pipeline as the main loop, focus goes where the reconciler puts it.
+pub struct Wm {
+    pub processor: EventProcessor<FakeBackend>,
+}
+
+impl Wm {
+    pub fn new() -> Wm {
+        Wm::with_config(Config::default())
+    }
+
+    pub fn with_config(config: Config) -> Wm {
+        Wm::with_backend(config, FakeBackend::default())
+    }
+
+    pub fn with_backend(config: Config, backend: FakeBackend) -> Wm {
+        Wm {
+            processor: EventProcessor::new(config, backend),
+        }
+    }
+
+    pub fn try_send(&mut self, line: &str) -> Result<Changes, ProcessError> {
+        self.processor.process(parse_event(Ok(line.to_string())))
+    }
+
+    pub fn send(&mut self, line: &str) -> Changes {
+        match self.try_send(line) {
+            Ok(changes) => changes,
+            Err(err) => panic!("{} failed: {}\n{:?}", line, err, self.state()),
+        }
+    }
+
+    /// Sends an event that has no text form, like the ones X sends.
+    pub fn process(&mut self, event: Event) -> Changes {
+        match self.processor.process(event.clone()) {
+            Ok(changes) => changes,
+            Err(err) => panic!("{:?} failed: {}\n{:?}", event, err, self.state()),
+        }
+    }
+
+    pub fn state(&self) -> &WMState {
+        self.processor.state()
+    }
+
+    pub fn config(&self) -> &Config {
+        self.processor.config()
+    }
+
+    pub fn backend(&self) -> &FakeBackend {
+        self.processor.backend()
+    }
+
+    /// Creates and maps `wid`, returns what mapping it changed.
+    pub fn open(&mut self, wid: &str) -> Changes {
+        self.send(&format!("CREATE {}", wid));
+        self.send(&format!("MAP {}", wid))
+    }
+
+    /// Opens `wid` and turns it into scratchpad `name`.
+    pub fn open_scratchpad(&mut self, wid: &str, name: &str) {
+        self.open(wid);
+        self.send(&format!("SP_ADD {}", name));
+    }
+
+    pub fn focus(&self) -> Option<WindowId> {
+        self.state().focus_target()
+    }
+
+    pub fn fullscreen(&self, ws: usize) -> Option<WindowId> {
+        self.state().workspaces[ws].fullscreen
+    }
+
+    /// Windows of workspace `ws`, counting from 0.
+    pub fn windows(&self, ws: usize) -> Vec<WindowId> {
+        self.state().workspaces[ws].windows.clone()
+    }
+}
@@ -0,0 +1,121 @@
+use derpywm::geometry::Struts;
+use derpywm::{Config, ConfigError};
+use std::fs;
+use std::path::PathBuf;
+
+/// Writes `contents` to a config file only this test uses.
+fn config_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("derpy-wm-{}-{}.toml", name, std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn invalid_key(config: &Config) -> &'static str {
+    match config.validate() {
+        Err(ConfigError::Invalid { key, .. }) => key,
+        other => panic!("expected an invalid key, got {:?}", other),
+    }
+}
+
+#[test]
+fn default_config_is_valid() {
+    assert!(Config::default().validate().is_ok());
+}
+
+#[test]
+fn zero_workspaces_are_rejected() {
+    let config = Config {
+        workspaces: 0,
+        ..Config::default()
+    };
+    assert_eq!(invalid_key(&config), "workspaces");
+}
+
+#[test]
+fn gaps_leaving_no_room_are_rejected() {
+    let config = Config {
+        gaps: 400,
+        workspace_size: (1200, 800),
+        reserved: Struts::default(),
+        ..Config::default()
+    };
+    assert_eq!(invalid_key(&config), "gaps");
+
+    let config = Config {
+        reserved: Struts {
+            left: 1000,
+            right: 1000,
+            ..Struts::default()
+        },
+        ..Config::default()
+    };
+    assert_eq!(invalid_key(&config), "reserved");
+}
+
+#[test]
+fn oversized_scratchpad_is_rejected() {
+    let config = Config {
+        scratchpad_size: (2000, 700),
+        ..Config::default()
+    };
+    assert_eq!(invalid_key(&config), "scratchpad_size");
+
+    let config = Config {
+        scratchpad_size: (1200, 0),
+        ..Config::default()
+    };
+    assert_eq!(invalid_key(&config), "scratchpad_size");
+}
+
+#[test]
+fn bad_color_names_its_key() {
+    for color in &["red", "0xff00", "#ff0000", "0xgg0000"] {
+        let config = Config {
+            urgent_border: color.to_string(),
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(
+            err.to_string().contains("`urgent_border`"),
+            "{} gave {}",
+            color,
+            err
+        );
+    }
+    let config = Config {
+        focused_border: "00ff00".to_string(),
+        ..Config::default()
+    };
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn explicit_config_file_must_exist_and_be_valid() {
+    let missing = std::env::temp_dir().join("derpy-wm-missing.toml");
+    assert!(matches!(
+        Config::load(Some(missing)),
+        Err(ConfigError::Load(_))
+    ));
+
+    let path = config_file("invalid", "focused_border = \"red\"\n");
+    let config = Config::load(Some(path.clone()));
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        config,
+        Err(ConfigError::Invalid {
+            key: "focused_border",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn old_panel_width_reserves_the_bottom_edge() {
+    let path = config_file("panel", "panel_width = 30\n");
+    let config = Config::load(Some(path.clone()));
+    fs::remove_file(&path).unwrap();
+
+    let config = config.unwrap();
+    assert_eq!(config.reserved.bottom, 30);
+    assert_eq!(config.panel_width, None);
+}
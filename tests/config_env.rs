@@ -0,0 +1,23 @@
+//! Environment overrides change the whole process's environment, so they are
+//! tested in their own binary where no other `Config::load` runs alongside.
+
+use derpywm::Config;
+use std::fs;
+
+#[test]
+fn file_and_environment_override_defaults() {
+    let path = std::env::temp_dir().join(format!("derpy-wm-env-{}.toml", std::process::id()));
+    fs::write(&path, "gaps = 6\nworkspaces = 4\n\n[reserved]\ntop = 10\n").unwrap();
+    std::env::set_var("DERPY_WM_WORKSPACES", "3");
+    std::env::set_var("DERPY_WM_RESERVED__TOP", "24");
+    let config = Config::load(Some(path.clone()));
+    std::env::remove_var("DERPY_WM_WORKSPACES");
+    std::env::remove_var("DERPY_WM_RESERVED__TOP");
+    fs::remove_file(&path).unwrap();
+
+    let config = config.unwrap();
+    assert_eq!(config.gaps, 6);
+    assert_eq!(config.workspaces, 3);
+    assert_eq!(config.reserved.top, 24);
+    assert_eq!(config.focused_border, Config::default().focused_border);
+}
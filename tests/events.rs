@@ -0,0 +1,200 @@
+mod common;
+
+use common::{FakeBackend, Wm};
+use derpywm::event_handlers::{handle_workspace_event, HandlerError};
+use derpywm::geometry::{Anchor, Struts};
+use derpywm::mutation::Transaction;
+use derpywm::processor::{EventProcessor, HeadlessBackend, ProcessError};
+use derpywm::reconciler::WMState;
+use derpywm::{parse_event, Config, Event, ScratchpadConfig, WindowId, WorkspaceEvent};
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+fn parse(line: &str) -> Event {
+    parse_event(Ok(line.to_string()))
+}
+
+#[test]
+fn malformed_lines_are_invalid_instead_of_panicking() {
+    assert!(matches!(parse(""), Event::Unknown));
+    assert!(matches!(parse("   "), Event::Unknown));
+    for line in &[
+        "MAP",
+        "WS_FOCUS",
+        "WS_FOCUS 0",
+        "WS_MOVE two",
+        "SP_TOGGLE",
+        "WS_RESIZE LEFT",
+        "SP_RESIZE term 0 10",
+        "MAP 0xzz",
+    ] {
+        assert!(
+            matches!(parse(line), Event::Invalid(_)),
+            "{} should be invalid",
+            line
+        );
+    }
+    let err = io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    );
+    assert!(matches!(parse_event(Err(err)), Event::Invalid(_)));
+}
+
+#[test]
+fn window_ids_match_whatever_their_padding() {
+    let padded: WindowId = "0x01a00003".parse().unwrap();
+    let unpadded: WindowId = "0x1a00003".parse().unwrap();
+    assert_eq!(padded, unpadded);
+    assert_eq!(unpadded.to_string(), "0x01a00003");
+    assert!("".parse::<WindowId>().is_err());
+    assert!("0x".parse::<WindowId>().is_err());
+    assert!("0x100000000".parse::<WindowId>().is_err());
+}
+
+#[test]
+fn missing_workspace_is_an_error() {
+    let config = Config::default();
+    let mut state = WMState::new(config.workspaces);
+    let mut now = Transaction::new(&mut state);
+    let handled = handle_workspace_event(&mut now, WorkspaceEvent::Focus(99), &config);
+    assert!(matches!(handled, Err(HandlerError::NoSuchWorkspace(99))));
+    assert!(now.mutations().is_empty());
+    now.rollback();
+    assert_eq!(state.focused_workspace, 0);
+}
+
+#[test]
+fn invariants_catch_windows_in_two_places() {
+    let mut state = WMState::new(2);
+    assert_eq!(state.check_invariants(), Ok(()));
+
+    state.workspaces[0].windows.push(WindowId(1));
+    state.workspaces[1].windows.push(WindowId(1));
+    assert!(state.check_invariants().is_err());
+
+    state.workspaces[1].windows.clear();
+    state.scratchpad.windows.push(("term".into(), WindowId(1)));
+    assert!(state.check_invariants().is_err());
+}
+
+#[test]
+fn invariants_catch_dangling_references() {
+    let mut state = WMState::new(2);
+    state.workspaces[0].focus_history.push(WindowId(1));
+    assert!(state.check_invariants().is_err());
+
+    let mut state = WMState::new(2);
+    state.workspaces[1].fullscreen = Some(WindowId(1));
+    assert!(state.check_invariants().is_err());
+
+    let mut state = WMState::new(2);
+    state.scratchpad.shown.push("term".into());
+    assert!(state.check_invariants().is_err());
+
+    let mut state = WMState::new(2);
+    state.focused_workspace = 2;
+    assert!(state.check_invariants().is_err());
+}
+
+#[test]
+fn processor_runs_hooks_and_keeps_state_on_errors() {
+    let mut processor = EventProcessor::new(Config::default(), HeadlessBackend);
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let pre = Rc::clone(&seen);
+    processor.add_pre_hook(move |event, _| pre.borrow_mut().push(format!("pre {:?}", event)));
+    let post = Rc::clone(&seen);
+    processor.add_post_hook(move |_, state, changes| {
+        post.borrow_mut().push(format!(
+            "post {} {}",
+            state.focused_workspace + 1,
+            !changes.mutations.is_empty()
+        ))
+    });
+
+    let changes = processor.process(parse("WS_FOCUS 2")).unwrap();
+    assert!(!changes.mutations.is_empty());
+    assert!(matches!(
+        processor.process(parse("WS_FOCUS 99")),
+        Err(ProcessError::Handler(HandlerError::NoSuchWorkspace(98)))
+    ));
+    assert!(matches!(
+        processor.process(parse("WS_FOCUS")),
+        Err(ProcessError::Invalid(_))
+    ));
+    assert_eq!(processor.state().focused_workspace, 1);
+
+    let reply = processor.process(parse("QUERY_STATE")).unwrap().reply;
+    assert!(reply.unwrap().starts_with("focused_workspace 2\n"));
+    assert_eq!(seen.borrow().len(), 6);
+    assert_eq!(seen.borrow()[1], "post 2 true");
+    assert_eq!(seen.borrow()[5], "post 2 false");
+}
+
+#[test]
+fn handlers_ask_the_backend_about_windows() {
+    let struts = Struts {
+        top: 30,
+        ..Struts::default()
+    };
+    let backend = FakeBackend {
+        docks: vec![(WindowId(9), struts)],
+        ..FakeBackend::default()
+    };
+    let mut config = Config::default();
+    config.scratchpads.push(ScratchpadConfig {
+        name: "term".to_string(),
+        command: "xterm -name dropdown".to_string(),
+        class: "dropdown".to_string(),
+        size: None,
+        anchor: Anchor::Center,
+    });
+    let mut processor = EventProcessor::new(config, backend);
+    for line in &["CREATE 0x9", "MAP 0x9", "CREATE 0x1", "MAP 0x1"] {
+        processor.process(parse(line)).unwrap();
+    }
+    assert_eq!(processor.state().workspaces[0].windows, vec![WindowId(1)]);
+    assert_eq!(processor.state().usable_area(processor.config()).y, 30);
+
+    processor.process(parse("SP_TOGGLE term")).unwrap();
+    processor.process(parse("WINDOW_CLOSE")).unwrap();
+    assert_eq!(processor.backend().spawned, vec!["xterm -name dropdown"]);
+    assert_eq!(processor.backend().closed, vec![WindowId(1)]);
+}
+
+#[test]
+fn maps_are_matched_to_their_own_create() {
+    let mut wm = Wm::new();
+    // Focus moving between creation and map doesn't lose the window
+    wm.send("CREATE 0x1");
+    wm.send("FOCUS_IN 0x2");
+    wm.send("MAP 0x1");
+    assert_eq!(wm.windows(0), vec![WindowId(1)]);
+
+    // Neither do other windows being created in between
+    wm.send("CREATE 0x2");
+    wm.send("CREATE 0x3");
+    wm.send("MAP 0x3");
+    wm.send("MAP 0x2");
+    assert_eq!(wm.windows(0), vec![WindowId(1), WindowId(3), WindowId(2)]);
+    assert!(wm.state().created.is_empty());
+}
+
+#[test]
+fn only_the_first_map_after_create_manages() {
+    let mut wm = Wm::new();
+    assert!(wm.send("MAP 0x1").mutations.is_empty());
+
+    wm.open("0x2");
+    wm.send("WS_MOVE 2");
+    assert!(wm.send("MAP 0x2").mutations.is_empty());
+    assert!(wm.send("CREATE 0x2").mutations.is_empty());
+    assert_eq!(wm.windows(1), vec![WindowId(2)]);
+
+    wm.send("CREATE 0x3");
+    wm.send("DESTROY 0x3");
+    assert!(wm.state().created.is_empty());
+    assert!(wm.send("MAP 0x3").mutations.is_empty());
+    assert!(wm.windows(0).is_empty());
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use common::{FakeBackend, Wm};
+use derpywm::reconciler::{Border, Op};
+use derpywm::{parse_event, ClientEvent, Config, Event, StateAction, WindowId};
+
+fn attention(wid: u32, action: StateAction) -> Event {
+    Event::Client(ClientEvent::Attention(WindowId(wid), action))
+}
+
+#[test]
+fn unfocused_windows_become_urgent_until_focused() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    let changes = wm.process(attention(1, StateAction::Add));
+    assert_eq!(wm.state().urgent, vec![WindowId(1)]);
+    assert!(changes
+        .ops
+        .contains(&Op::Border(WindowId(1), Border::Urgent)));
+
+    // The focused window has the user's attention already
+    wm.process(attention(2, StateAction::Add));
+    assert_eq!(wm.state().urgent, vec![WindowId(1)]);
+
+    wm.send("FOCUS_IN 0x1");
+    assert!(wm.state().urgent.is_empty());
+}
+
+#[test]
+fn urgency_can_be_withdrawn_and_dies_with_the_window() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    wm.open("0x3");
+    wm.process(attention(1, StateAction::Add));
+    wm.process(attention(2, StateAction::Toggle));
+    assert_eq!(wm.state().urgent, vec![WindowId(1), WindowId(2)]);
+
+    let changes = wm.process(attention(1, StateAction::Remove));
+    assert_eq!(wm.state().urgent, vec![WindowId(2)]);
+    assert!(changes
+        .ops
+        .contains(&Op::Border(WindowId(1), Border::Unfocused)));
+
+    wm.send("DESTROY 0x2");
+    assert!(wm.state().urgent.is_empty());
+
+    // Windows that aren't managed can't be urgent
+    wm.process(attention(7, StateAction::Add));
+    assert!(wm.state().urgent.is_empty());
+}
+
+#[test]
+fn focus_urgent_goes_to_the_latest_urgent_window() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.send("WS_FOCUS 2");
+    wm.open("0x2");
+    wm.send("WS_FOCUS 3");
+    wm.process(attention(1, StateAction::Add));
+    wm.process(attention(2, StateAction::Add));
+
+    let changes = wm.send("FOCUS_URGENT");
+    assert_eq!(wm.state().focused_workspace, 1);
+    assert_eq!(changes.ops.last(), Some(&Op::Focus(WindowId(2))));
+    wm.send("FOCUS_IN 0x2");
+
+    wm.send("FOCUS_URGENT");
+    assert_eq!(wm.state().focused_workspace, 0);
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+    wm.send("FOCUS_IN 0x1");
+    assert!(wm.state().urgent.is_empty());
+
+    let changes = wm.send("FOCUS_URGENT");
+    assert!(changes.mutations.is_empty());
+}
+
+#[test]
+fn query_state_lists_urgent_windows() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    wm.process(attention(1, StateAction::Add));
+    let reply = wm.send("QUERY_STATE").reply.unwrap();
+    assert!(reply.ends_with("\nurgent 0x00000001"), "{}", reply);
+}
+
+#[test]
+fn focus_next_and_prev_cycle_through_the_workspace() {
+    let mut wm = Wm::new();
+    for wid in &["0x1", "0x2", "0x3"] {
+        wm.open(wid);
+    }
+    wm.send("FOCUS_NEXT");
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+    wm.send("FOCUS_PREV");
+    assert_eq!(wm.focus(), Some(WindowId(3)));
+    wm.send("FOCUS_PREV");
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+    assert_eq!(wm.state().focused_window, Some(WindowId(2)));
+}
+
+#[test]
+fn focus_last_goes_back_and_forth() {
+    let mut wm = Wm::new();
+    for wid in &["0x1", "0x2", "0x3"] {
+        wm.open(wid);
+    }
+    wm.send("FOCUS_IN 0x1");
+    wm.send("FOCUS_LAST");
+    assert_eq!(wm.focus(), Some(WindowId(3)));
+    wm.send("FOCUS_LAST");
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+}
+
+#[test]
+fn cycling_needs_two_windows_and_no_scratchpad() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    for line in &["FOCUS_NEXT", "FOCUS_PREV", "FOCUS_LAST"] {
+        assert!(wm.send(line).mutations.is_empty(), "{}", line);
+    }
+    wm.open_scratchpad("0x2", "term");
+    wm.open("0x3");
+    wm.send("SP_TOGGLE term");
+    for line in &["FOCUS_NEXT", "FOCUS_PREV", "FOCUS_LAST"] {
+        assert!(wm.send(line).mutations.is_empty(), "{}", line);
+    }
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+}
+
+#[test]
+fn fullscreen_follows_cycling() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    wm.send("WS_FULLSCREEN");
+    wm.send("FOCUS_NEXT");
+    assert_eq!(wm.fullscreen(0), Some(WindowId(1)));
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+    wm.send("FOCUS_LAST");
+    assert_eq!(wm.fullscreen(0), Some(WindowId(2)));
+}
+
+#[test]
+fn focus_stops_at_the_layout_edges() {
+    let mut wm = Wm::with_config(Config {
+        workspaces: 3,
+        ..Config::default()
+    });
+    // 0x1 left and 0x2 right on the first workspace, 0x3 alone on the second
+    wm.open("0x1");
+    wm.open("0x2");
+    wm.send("WS_FOCUS 2");
+    wm.open("0x3");
+    wm.send("WS_FOCUS 1");
+
+    wm.send("WS_FOCUS_WINDOW RIGHT");
+    assert!(wm.send("WS_FOCUS_WINDOW RIGHT").mutations.is_empty());
+    wm.send("WS_FOCUS_WINDOW LEFT");
+    assert!(wm.send("WS_FOCUS_WINDOW LEFT").mutations.is_empty());
+    assert!(wm.send("WS_FOCUS_WINDOW UP").mutations.is_empty());
+    assert_eq!(wm.state().focused_workspace, 0);
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+}
+
+#[test]
+fn unknown_direction_is_an_error() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    match wm.try_send("WS_FOCUS_WINDOW FORWARD") {
+        Err(err) => assert!(err.to_string().contains("unknown direction `FORWARD`")),
+        Ok(changes) => panic!("moved focus with {:?}", changes),
+    }
+    assert!(matches!(
+        parse_event(Ok("WS_FOCUS_WINDOW".to_string())),
+        Event::Invalid(_)
+    ));
+}
+
+#[test]
+fn windows_asking_for_attention_when_they_map_are_urgent() {
+    let backend = FakeBackend {
+        urgent: vec![WindowId(2), WindowId(3)],
+        ..FakeBackend::default()
+    };
+    let mut wm = Wm::with_backend(Config::default(), backend);
+    wm.open_scratchpad("0x1", "term");
+    wm.send("SP_TOGGLE term");
+    // 0x2 maps below the scratchpad, 0x3 gets focus once it is hidden
+    wm.open("0x2");
+    assert_eq!(wm.state().urgent, vec![WindowId(2)]);
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+
+    wm.send("SP_TOGGLE term");
+    wm.open("0x3");
+    assert_eq!(wm.focus(), Some(WindowId(3)));
+    assert!(!wm.state().urgent.contains(&WindowId(3)));
+}
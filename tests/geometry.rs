@@ -0,0 +1,138 @@
+use derpywm::geometry::{to_i32, Anchor, Length, Rect, Struts, MIN_SIZE};
+use derpywm::{weighted_tile_layout, DEFAULT_COLUMN_SHARE, DEFAULT_WEIGHT};
+use proptest::prelude::*;
+
+fn even_layout(n: usize, gap: usize, area: Rect) -> Vec<Rect> {
+    weighted_tile_layout(&vec![DEFAULT_WEIGHT; n], DEFAULT_COLUMN_SHARE, gap, area)
+}
+
+#[test]
+fn tiles_like_two_columns() {
+    let area = Rect::from_size((1920, 1080)).reserve(Struts {
+        bottom: 18,
+        ..Struts::default()
+    });
+    assert_eq!(
+        even_layout(3, 10, area),
+        vec![
+            Rect::new(10, 10, 945, 1042),
+            Rect::new(965, 10, 945, 516),
+            Rect::new(965, 536, 945, 516),
+        ]
+    );
+}
+
+#[test]
+fn weights_resize_rows_and_columns() {
+    let area = Rect::from_size((1000, 1000));
+    assert_eq!(
+        weighted_tile_layout(&[100, 300, 100], 700, 0, area),
+        vec![
+            Rect::new(0, 0, 700, 1000),
+            Rect::new(700, 0, 300, 750),
+            Rect::new(700, 750, 300, 250),
+        ]
+    );
+}
+
+#[test]
+fn struts_are_reserved_on_their_edge() {
+    let struts = Struts {
+        top: 20,
+        left: 100,
+        ..Struts::default()
+    };
+    let area = Rect::from_size((1920, 1080)).reserve(struts);
+    assert_eq!(area, Rect::new(100, 20, 1820, 1060));
+    assert_eq!(
+        even_layout(1, 10, area),
+        vec![Rect::new(110, 30, 1800, 1040)]
+    );
+}
+
+#[test]
+fn oversized_scratchpad_is_clamped_to_screen() {
+    let screen = Rect::from_size((800, 600));
+    assert_eq!(screen.centered((1200, 700)), Rect::new(0, 0, 800, 600));
+    assert_eq!(screen.centered((400, 200)), Rect::new(200, 200, 400, 200));
+}
+
+#[test]
+fn scratchpads_are_anchored_to_screen_edges() {
+    let screen = Rect::from_size((1000, 800));
+    let size = (Length::Percent(100), Length::Percent(40));
+    assert_eq!(
+        screen.anchored(size, Anchor::Top),
+        Rect::new(0, 0, 1000, 320)
+    );
+    let size = (Length::Pixels(300), Length::Percent(100));
+    assert_eq!(
+        screen.anchored(size, Anchor::Right),
+        Rect::new(700, 0, 300, 800)
+    );
+    let size = (Length::Pixels(2000), Length::Pixels(200));
+    assert_eq!(
+        screen.anchored(size, Anchor::Center),
+        Rect::new(0, 300, 1000, 200)
+    );
+}
+
+#[test]
+fn lengths_parse_as_pixels_or_percent() {
+    assert_eq!("800".parse(), Ok(Length::Pixels(800)));
+    assert_eq!("50%".parse(), Ok(Length::Percent(50)));
+    assert!("0".parse::<Length>().is_err());
+    assert!("150%".parse::<Length>().is_err());
+    assert!("wide".parse::<Length>().is_err());
+}
+
+proptest! {
+    #[test]
+    fn layout_never_degenerates(
+        weights in prop::collection::vec(0u32..1000, 0..5000),
+        column_share in 0u32..2000,
+        gap in 0usize..100_000,
+        size in (0usize..10_000, 0usize..10_000),
+        (top, bottom, left, right) in (0usize..100_000, 0usize..100_000, 0usize..100_000, 0usize..100_000),
+    ) {
+        let struts = Struts { top, bottom, left, right };
+        let area = Rect::from_size(size).reserve(struts);
+        let rects = weighted_tile_layout(&weights, column_share, gap, area);
+        prop_assert_eq!(rects.len(), weights.len());
+        for rect in rects {
+            prop_assert!(rect.w >= MIN_SIZE && rect.h >= MIN_SIZE);
+        }
+    }
+
+    #[test]
+    fn layout_fits_when_there_is_room(
+        n in 1usize..20,
+        gap in 0usize..20,
+        size in (400usize..4000, 400usize..4000),
+    ) {
+        let area = Rect::from_size(size);
+        for rect in even_layout(n, gap, area) {
+            prop_assert!(rect.x >= 0 && rect.y >= 0);
+            prop_assert!(rect.x + rect.w <= area.w);
+            prop_assert!(rect.y + rect.h <= area.h);
+        }
+    }
+
+    #[test]
+    fn extreme_values_saturate(
+        weights in prop::collection::vec(prop_oneof![Just(u32::MAX), Just(0u32), 0u32..10], 0..10),
+        column_share in prop_oneof![Just(u32::MAX), 0u32..1000],
+        gap in prop_oneof![Just(usize::MAX), 0usize..10],
+    ) {
+        let area = Rect::from_size((usize::MAX, usize::MAX));
+        let rows = area.shrink(to_i32(gap)).split_rows_weighted(&weights, to_i32(gap));
+        prop_assert_eq!(rows.len(), weights.len());
+        for rect in rows {
+            prop_assert!(rect.w >= MIN_SIZE && rect.h >= MIN_SIZE);
+        }
+        for rect in weighted_tile_layout(&weights, column_share, gap, area) {
+            prop_assert!(rect.w >= MIN_SIZE && rect.h >= MIN_SIZE);
+        }
+        let _ = Rect::from_size((1, 1)).centered((usize::MAX, usize::MAX));
+    }
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::Wm;
+use derpywm::event_handlers::handle_workspace_event;
+use derpywm::mutation::{Mutation, Transaction};
+use derpywm::reconciler::WMState;
+use derpywm::{Config, Direction, WindowId, WorkspaceEvent, DEFAULT_WEIGHT};
+
+fn ids(ids: &[u32]) -> Vec<WindowId> {
+    ids.iter().map(|id| WindowId(*id)).collect()
+}
+
+/// Four tiled windows, 0x1 and 0x2 in the left column, 0x3 and 0x4 in the right one.
+fn four_windows() -> Wm {
+    let mut wm = Wm::new();
+    for wid in &["0x1", "0x2", "0x3", "0x4"] {
+        wm.open(wid);
+    }
+    wm
+}
+
+#[test]
+fn swap_exchanges_with_the_neighbour_in_that_direction() {
+    let mut wm = four_windows();
+    wm.send("FOCUS_IN 0x1");
+    wm.send("WS_SWAP RIGHT");
+    assert_eq!(wm.windows(0), ids(&[3, 2, 1, 4]));
+    wm.send("WS_SWAP DOWN");
+    assert_eq!(wm.windows(0), ids(&[3, 2, 4, 1]));
+    wm.send("WS_SWAP LEFT");
+    assert_eq!(wm.windows(0), ids(&[3, 1, 4, 2]));
+    wm.send("WS_SWAP UP");
+    assert_eq!(wm.windows(0), ids(&[1, 3, 4, 2]));
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+}
+
+#[test]
+fn swap_stops_at_the_edges() {
+    let mut wm = four_windows();
+    wm.send("FOCUS_IN 0x1");
+    for direction in &["LEFT", "UP"] {
+        let changes = wm.send(&format!("WS_SWAP {}", direction));
+        assert!(changes.mutations.is_empty(), "{}", direction);
+    }
+    wm.send("FOCUS_IN 0x4");
+    for direction in &["RIGHT", "DOWN"] {
+        let changes = wm.send(&format!("WS_SWAP {}", direction));
+        assert!(changes.mutations.is_empty(), "{}", direction);
+    }
+    assert_eq!(wm.windows(0), ids(&[1, 2, 3, 4]));
+}
+
+#[test]
+fn swap_skips_floating_windows() {
+    let mut wm = four_windows();
+    wm.send("FOCUS_IN 0x3");
+    wm.send("WINDOW_FLOAT_TOGGLE");
+    // Tiled are 0x1 on the left, 0x2 and 0x4 on the right
+    wm.send("FOCUS_IN 0x4");
+    wm.send("WS_SWAP UP");
+    assert_eq!(wm.windows(0), ids(&[1, 4, 3, 2]));
+}
+
+#[test]
+fn nothing_is_reordered_under_a_fullscreen_window() {
+    let mut wm = four_windows();
+    wm.send("FOCUS_IN 0x2");
+    wm.send("WS_FULLSCREEN");
+    for line in &["WS_SWAP RIGHT", "WS_PROMOTE"] {
+        assert!(wm.send(line).mutations.is_empty(), "{}", line);
+    }
+    assert_eq!(wm.windows(0), ids(&[1, 2, 3, 4]));
+}
+
+#[test]
+fn promote_moves_the_focused_window_first() {
+    let mut wm = four_windows();
+    wm.send("FOCUS_IN 0x3");
+    wm.send("WS_PROMOTE");
+    assert_eq!(wm.windows(0), ids(&[3, 1, 2, 4]));
+    assert_eq!(wm.focus(), Some(WindowId(3)));
+    assert!(wm.send("WS_PROMOTE").mutations.is_empty());
+}
+
+#[test]
+fn resize_keeps_columns_within_limits() {
+    let mut wm = four_windows();
+    wm.send("FOCUS_IN 0x1");
+    wm.send("WS_RESIZE RIGHT 5000");
+    assert_eq!(wm.state().workspaces[0].column_share, 900);
+    assert!(wm.send("WS_RESIZE RIGHT 10").mutations.is_empty());
+
+    wm.send("FOCUS_IN 0x3");
+    wm.send("WS_RESIZE LEFT 5000");
+    assert_eq!(wm.state().workspaces[0].column_share, 100);
+    assert!(wm.send("WS_RESIZE LEFT 10").mutations.is_empty());
+}
+
+#[test]
+fn resize_keeps_rows_within_limits() {
+    let mut wm = four_windows();
+    wm.send("FOCUS_IN 0x1");
+    wm.send("WS_RESIZE DOWN 5000");
+    let workspace = &wm.state().workspaces[0];
+    assert_eq!(workspace.weight(WindowId(1)), 190);
+    assert_eq!(workspace.weight(WindowId(2)), 10);
+    assert!(wm.send("WS_RESIZE DOWN 10").mutations.is_empty());
+
+    // The column keeps its total weight the other way around too
+    wm.send("FOCUS_IN 0x2");
+    wm.send("WS_RESIZE UP 5000");
+    let workspace = &wm.state().workspaces[0];
+    assert_eq!(workspace.weight(WindowId(1)), 10);
+    assert_eq!(workspace.weight(WindowId(2)), 190);
+    // The other column is untouched
+    assert_eq!(workspace.weight(WindowId(3)), DEFAULT_WEIGHT);
+}
+
+#[test]
+fn resize_leaves_rows_below_the_minimum_alone() {
+    let config = Config::default();
+    let mut state = WMState::new(config.workspaces);
+    let mut now = Transaction::new(&mut state);
+    for wid in 1..=4 {
+        now.apply(Mutation::AddWindow {
+            workspace: 0,
+            window: WindowId(wid),
+        });
+    }
+    for wid in &[1, 2] {
+        now.apply(Mutation::Weight {
+            workspace: 0,
+            window: WindowId(*wid),
+            weight: 5,
+        });
+    }
+    now.apply(Mutation::FocusOnWorkspace {
+        workspace: 0,
+        window: WindowId(1),
+    });
+    now.commit();
+
+    for px in &[100, -100] {
+        let mut now = Transaction::new(&mut state);
+        let event = WorkspaceEvent::Resize(Direction::Down, *px);
+        let handled = handle_workspace_event(&mut now, event, &config);
+        assert!(matches!(handled, Ok(false)), "{}", px);
+        now.rollback();
+    }
+    assert_eq!(state.workspaces[0].weight(WindowId(1)), 5);
+    assert_eq!(state.workspaces[0].weight(WindowId(2)), 5);
+}
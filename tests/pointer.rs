@@ -0,0 +1,200 @@
+mod common;
+
+use common::Wm;
+use derpywm::geometry::Rect;
+use derpywm::reconciler::DragKind;
+use derpywm::{Event, PointerEvent, WindowId};
+
+fn press(wid: u32, button: u8, (x, y): (i32, i32)) -> Event {
+    Event::Pointer(PointerEvent::Press {
+        window_id: WindowId(wid),
+        button,
+        x,
+        y,
+    })
+}
+
+fn motion((x, y): (i32, i32)) -> Event {
+    Event::Pointer(PointerEvent::Motion { x, y })
+}
+
+fn release((x, y): (i32, i32)) -> Event {
+    Event::Pointer(PointerEvent::Release { x, y })
+}
+
+fn tiled_rect(wm: &Wm, wid: u32) -> Rect {
+    let rects = wm.state().tiled_rects(wm.config());
+    rects
+        .into_iter()
+        .find(|(window, _)| *window == WindowId(wid))
+        .map(|(_, rect)| rect)
+        .unwrap()
+}
+
+fn center(rect: Rect) -> (i32, i32) {
+    (rect.x + rect.w / 2, rect.y + rect.h / 2)
+}
+
+#[test]
+fn press_starts_a_drag_on_managed_windows() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    let at = center(tiled_rect(&wm, 1));
+
+    assert!(wm.process(press(9, 1, at)).mutations.is_empty());
+    assert!(wm.process(press(1, 2, at)).mutations.is_empty());
+    assert!(wm.state().drag.is_none());
+
+    wm.process(press(1, 1, at));
+    let drag = wm.state().drag.clone().unwrap();
+    assert_eq!(drag.window_id, WindowId(1));
+    assert_eq!(drag.kind, DragKind::Move);
+    assert_eq!(drag.rect, tiled_rect(&wm, 1));
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+}
+
+#[test]
+fn motion_without_a_drag_changes_nothing() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    assert!(wm.process(motion((10, 10))).mutations.is_empty());
+    assert!(wm.process(release((10, 10))).mutations.is_empty());
+}
+
+#[test]
+fn dropping_a_tiled_window_swaps_it_with_the_one_under_the_pointer() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    let from = center(tiled_rect(&wm, 1));
+    let to = center(tiled_rect(&wm, 2));
+
+    wm.process(press(1, 1, from));
+    // Tiled windows stay in place until they are dropped
+    assert!(wm.process(motion(to)).mutations.is_empty());
+    assert_eq!(wm.windows(0), vec![WindowId(1), WindowId(2)]);
+
+    wm.process(release(to));
+    assert!(wm.state().drag.is_none());
+    assert_eq!(wm.windows(0), vec![WindowId(2), WindowId(1)]);
+    assert!(wm.state().workspaces[0].floating.is_empty());
+}
+
+#[test]
+fn dropping_a_tiled_window_on_itself_keeps_the_layout() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    let at = center(tiled_rect(&wm, 1));
+
+    wm.process(press(1, 1, at));
+    wm.process(release((at.0 + 5, at.1 + 5)));
+    assert!(wm.state().drag.is_none());
+    assert_eq!(wm.windows(0), vec![WindowId(1), WindowId(2)]);
+}
+
+#[test]
+fn dropping_a_tiled_window_outside_the_layout_floats_it() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    let rect = tiled_rect(&wm, 1);
+    let from = center(rect);
+
+    wm.process(press(1, 1, from));
+    wm.process(release((-100, -100)));
+    let moved = rect.translate(-100 - from.0, -100 - from.1);
+    assert_eq!(
+        wm.state().workspaces[0].floating_rect(WindowId(1)),
+        Some(moved)
+    );
+}
+
+#[test]
+fn floating_windows_follow_the_pointer() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.send("WINDOW_FLOAT_TOGGLE");
+    let rect = wm.state().workspaces[0].floating_rect(WindowId(1)).unwrap();
+    let from = center(rect);
+
+    wm.process(press(1, 1, from));
+    wm.process(motion((from.0 + 10, from.1 + 20)));
+    wm.process(motion((from.0 + 30, from.1 + 40)));
+    let floating = wm.state().workspaces[0].floating_rect(WindowId(1));
+    assert_eq!(floating, Some(rect.translate(30, 40)));
+
+    wm.process(release((from.0 + 30, from.1 + 40)));
+    assert!(wm.state().drag.is_none());
+    let floating = wm.state().workspaces[0].floating_rect(WindowId(1));
+    assert_eq!(floating, Some(rect.translate(30, 40)));
+}
+
+#[test]
+fn resizing_a_tiled_window_floats_it() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    let rect = tiled_rect(&wm, 2);
+    let from = center(rect);
+
+    wm.process(press(2, 3, from));
+    wm.process(motion((from.0 - 50, from.1 - 60)));
+    let floating = wm.state().workspaces[0].floating_rect(WindowId(2));
+    assert_eq!(floating, Some(rect.grow(-50, -60)));
+
+    wm.process(release((from.0 - 50, from.1 - 60)));
+    assert!(wm.state().drag.is_none());
+    let tiled = wm.state().tiled_rects(wm.config());
+    assert_eq!(tiled.len(), 1);
+    assert_eq!(tiled[0].0, WindowId(1));
+}
+
+#[test]
+fn moving_the_dragged_window_away_ends_the_drag() {
+    let mut wm = Wm::new();
+    wm.open("0x3");
+    let at = center(tiled_rect(&wm, 3));
+    wm.process(press(3, 1, at));
+    wm.send("WS_MOVE 2");
+    assert!(wm.state().drag.is_none());
+    assert!(wm.process(release((-100, -100))).mutations.is_empty());
+    assert!(wm.state().workspaces[1].floating.is_empty());
+
+    // Later drags work as usual
+    wm.open("0x4");
+    let at = center(tiled_rect(&wm, 4));
+    wm.process(press(4, 1, at));
+    wm.process(release((-100, -100)));
+    assert!(wm.state().workspaces[0]
+        .floating_rect(WindowId(4))
+        .is_some());
+}
+
+#[test]
+fn switching_workspaces_ends_the_drag() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    let at = center(tiled_rect(&wm, 1));
+    wm.process(press(1, 3, at));
+    wm.send("WS_FOCUS 2");
+    assert!(wm.state().drag.is_none());
+    assert!(wm.process(motion((at.0 + 10, at.1))).mutations.is_empty());
+    wm.send("WS_FOCUS 1");
+    assert!(wm.process(release((at.0 + 10, at.1))).mutations.is_empty());
+    assert!(wm.state().workspaces[0].floating.is_empty());
+}
+
+#[test]
+fn dragged_window_can_be_made_sticky() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    let at = center(tiled_rect(&wm, 1));
+    wm.process(press(1, 1, at));
+    wm.send("WINDOW_STICKY_TOGGLE");
+    let rect = wm.state().sticky_rect(WindowId(1)).unwrap();
+    wm.process(motion((at.0 + 10, at.1 + 10)));
+    wm.process(release((at.0 + 10, at.1 + 10)));
+    assert!(wm.state().drag.is_none());
+    assert_ne!(wm.state().sticky_rect(WindowId(1)), Some(rect));
+}
@@ -0,0 +1,216 @@
+use derpywm::event_handlers::*;
+use derpywm::mutation::Transaction;
+use derpywm::processor::{EventProcessor, HeadlessBackend, ProcessError};
+use derpywm::reconciler::WMState;
+use derpywm::{
+    parse_event, ClientEvent, Config, Event, PointerEvent, StateAction, WindowEvent,
+    WindowEventType, WindowId,
+};
+use proptest::prelude::*;
+
+use std::collections::BTreeSet;
+
+fn event_line() -> impl Strategy<Value = String> {
+    // Few windows, workspaces and names so events keep running into each other
+    let wid = 1u32..4;
+    let ws = 1usize..4;
+    let name = prop::sample::select(vec!["a", "b"]);
+    let direction = prop::sample::select(vec!["LEFT", "RIGHT", "UP", "DOWN"]);
+    let length = prop::sample::select(vec!["1", "400", "5000", "50%", "100%"]);
+    prop_oneof![
+        wid.clone().prop_map(|wid| format!("CREATE 0x{:x}", wid)),
+        wid.clone().prop_map(|wid| format!("MAP 0x{:x}", wid)),
+        wid.clone().prop_map(|wid| format!("DESTROY 0x{:x}", wid)),
+        wid.prop_map(|wid| format!("FOCUS_IN 0x{:x}", wid)),
+        ws.clone().prop_map(|ws| format!("WS_FOCUS {}", ws)),
+        ws.prop_map(|ws| format!("WS_MOVE {}", ws)),
+        Just("WS_CYCLE".to_string()),
+        Just("WS_FULLSCREEN".to_string()),
+        Just("WS_PROMOTE".to_string()),
+        direction
+            .clone()
+            .prop_map(|dir| format!("WS_FOCUS_WINDOW {}", dir)),
+        direction.clone().prop_map(|dir| format!("WS_SWAP {}", dir)),
+        (direction, -300i32..300).prop_map(|(dir, px)| format!("WS_RESIZE {} {}", dir, px)),
+        prop::sample::select(vec![
+            "FOCUS_NEXT",
+            "FOCUS_PREV",
+            "FOCUS_LAST",
+            "FOCUS_URGENT"
+        ])
+        .prop_map(ToString::to_string),
+        Just("WINDOW_STICKY_TOGGLE".to_string()),
+        Just("WINDOW_FLOAT_TOGGLE".to_string()),
+        name.clone().prop_map(|name| format!("SP_ADD {}", name)),
+        name.clone().prop_map(|name| format!("SP_REMOVE {}", name)),
+        name.clone().prop_map(|name| format!("SP_TOGGLE {}", name)),
+        (name, length.clone(), length)
+            .prop_map(|(name, w, h)| format!("SP_RESIZE {} {} {}", name, w, h)),
+        Just("SP_RESTORE_ALL".to_string()),
+    ]
+}
+
+fn client_event() -> impl Strategy<Value = ClientEvent> {
+    let wid = (1u32..4).prop_map(WindowId);
+    let action = prop::sample::select(vec![
+        StateAction::Add,
+        StateAction::Remove,
+        StateAction::Toggle,
+    ]);
+    prop_oneof![
+        (0usize..4).prop_map(ClientEvent::CurrentDesktop),
+        wid.clone().prop_map(ClientEvent::ActiveWindow),
+        (wid.clone(), 0usize..4).prop_map(|(wid, ws)| ClientEvent::WindowDesktop(wid, ws)),
+        wid.clone().prop_map(ClientEvent::CloseWindow),
+        (wid.clone(), action.clone())
+            .prop_map(|(wid, action)| ClientEvent::Fullscreen(wid, action)),
+        (wid, action).prop_map(|(wid, action)| ClientEvent::Attention(wid, action)),
+    ]
+}
+
+fn pointer_event() -> impl Strategy<Value = PointerEvent> {
+    // Reaches past the default screen on both sides
+    let x = -100i32..2100;
+    let y = -100i32..1200;
+    prop_oneof![
+        (1u32..4, 1u8..4, x.clone(), y.clone()).prop_map(|(wid, button, x, y)| {
+            PointerEvent::Press {
+                window_id: WindowId(wid),
+                button,
+                x,
+                y,
+            }
+        }),
+        (x.clone(), y.clone()).prop_map(|(x, y)| PointerEvent::Motion { x, y }),
+        (x, y).prop_map(|(x, y)| PointerEvent::Release { x, y }),
+    ]
+}
+
+fn event() -> impl Strategy<Value = Event> {
+    // Drags only go wrong when other events land between press and release
+    prop_oneof![
+        8 => event_line().prop_map(|line| parse_event(Ok(line))),
+        1 => client_event().prop_map(Event::Client),
+        6 => pointer_event().prop_map(Event::Pointer),
+    ]
+}
+
+fn is_window_event(event: &Event, wid: WindowId, event_type: WindowEventType) -> bool {
+    match event {
+        Event::Window(WindowEvent {
+            window_id,
+            event_type: got,
+        }) => *window_id == wid && *got == event_type,
+        _ => false,
+    }
+}
+
+/// Runs one event through its handler the way the processor does, returns
+/// whether it was kept.
+fn apply(state: &mut WMState, event: &Event, config: &Config) -> bool {
+    let backend = &mut HeadlessBackend;
+    let mut now = Transaction::new(state);
+    let handled = match event.clone() {
+        Event::Window(event) => handle_window_event(&mut now, event, backend, config),
+        Event::Workspace(event) => handle_workspace_event(&mut now, event, config),
+        Event::Scratchpad(event) => handle_scratchpad_event(&mut now, event, backend, config),
+        Event::Client(event) => handle_client_event(&mut now, event, backend, config),
+        Event::Command(command) => handle_window_command(&mut now, command, backend, config),
+        Event::Pointer(event) => handle_pointer_event(&mut now, event, config),
+        _ => Ok(false),
+    };
+    if let Ok(true) = handled {
+        end_stale_drag(&mut now);
+        now.commit();
+        true
+    } else {
+        now.rollback();
+        false
+    }
+}
+
+/// Every managed window with the places it is in.
+fn places(state: &WMState) -> Vec<(WindowId, String)> {
+    let mut places = Vec::new();
+    for (i, workspace) in state.workspaces.iter().enumerate() {
+        for wid in &workspace.windows {
+            places.push((*wid, format!("workspace {}", i + 1)));
+        }
+    }
+    for (name, wid) in &state.scratchpad.windows {
+        places.push((*wid, format!("scratchpad {}", name)));
+    }
+    for (wid, _) in &state.sticky {
+        places.push((*wid, "sticky".to_string()));
+    }
+    places
+}
+
+fn managed(state: &WMState) -> BTreeSet<WindowId> {
+    places(state).into_iter().map(|(wid, _)| wid).collect()
+}
+
+proptest! {
+    #[test]
+    fn handlers_never_lose_or_duplicate_windows(
+        events in prop::collection::vec(event(), 1..60)
+    ) {
+        let config = Config::default();
+        let mut state = WMState::new(config.workspaces);
+        for event in events {
+            let before = managed(&state);
+            let snapshot = format!("{:?}", state);
+            if !apply(&mut state, &event, &config) {
+                prop_assert_eq!(format!("{:?}", state), snapshot, "{:?} wasn't rolled back", event);
+            }
+
+            let after = managed(&state);
+            let mut places = places(&state);
+            places.sort();
+            for pair in places.windows(2) {
+                prop_assert!(
+                    pair[0].0 != pair[1].0,
+                    "after {:?}: {} is in {} and {}", event, pair[0].0, pair[0].1, pair[1].1
+                );
+            }
+            for wid in before.difference(&after) {
+                prop_assert!(
+                    is_window_event(&event, *wid, WindowEventType::DestroyNotify),
+                    "{} was lost to {:?}", wid, event
+                );
+            }
+            for wid in after.difference(&before) {
+                prop_assert!(
+                    is_window_event(&event, *wid, WindowEventType::MapNotify),
+                    "{} appeared after {:?}", wid, event
+                );
+            }
+            if let Err(err) = state.check_invariants() {
+                prop_assert!(false, "after {:?}: {}", event, err);
+            }
+        }
+    }
+
+    #[test]
+    fn processor_only_rejects_events_cleanly(
+        events in prop::collection::vec(event(), 1..60)
+    ) {
+        let mut processor = EventProcessor::new(Config::default(), HeadlessBackend);
+        for event in events {
+            let snapshot = format!("{:?}", processor.state());
+            match processor.process(event.clone()) {
+                Err(err @ ProcessError::Panic(_)) | Err(err @ ProcessError::Invariant(_)) => {
+                    prop_assert!(false, "{:?}: {}", event, err);
+                }
+                // A rejected event leaves nothing behind for later events to trip over
+                Err(_) => {
+                    prop_assert_eq!(format!("{:?}", processor.state()), snapshot);
+                }
+                Ok(_) => {}
+            }
+            if let Err(err) = processor.state().check_invariants() {
+                prop_assert!(false, "after {:?}: {}", event, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use common::{FakeBackend, Wm};
+use derpywm::geometry::{Rect, Struts};
+use derpywm::processor::Changes;
+use derpywm::reconciler::{Border, Op};
+use derpywm::{ClientEvent, Config, Event, StateAction, WindowId};
+
+fn moves(changes: &Changes) -> Vec<(WindowId, Rect)> {
+    changes
+        .ops
+        .iter()
+        .filter_map(|op| match op {
+            Op::Move(wid, rect) => Some((*wid, *rect)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn new_window_is_mapped_tiled_and_focused_once() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    let changes = wm.open("0x2");
+    let rects = wm.state().tiled_rects(wm.config());
+    assert_eq!(moves(&changes), rects);
+    assert_eq!(
+        changes
+            .ops
+            .iter()
+            .filter(|op| **op == Op::Map(WindowId(2)))
+            .count(),
+        1
+    );
+    assert!(changes
+        .ops
+        .contains(&Op::Border(WindowId(1), Border::Unfocused)));
+    assert!(changes
+        .ops
+        .contains(&Op::Border(WindowId(2), Border::Focused)));
+    assert_eq!(changes.ops.last(), Some(&Op::Focus(WindowId(2))));
+    assert_eq!(wm.state().focused_window, Some(WindowId(2)));
+}
+
+#[test]
+fn moved_window_is_unmapped_and_the_rest_retiled() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    let changes = wm.send("WS_MOVE 2");
+    assert!(changes.ops.contains(&Op::Unmap(WindowId(2))));
+    assert!(!changes
+        .ops
+        .iter()
+        .any(|op| matches!(op, Op::Map(_) | Op::Move(WindowId(2), _))));
+    let screen = wm.state().usable_area(wm.config());
+    assert_eq!(
+        moves(&changes),
+        vec![(WindowId(1), screen.shrink(wm.config().gaps as i32))]
+    );
+    assert_eq!(changes.ops.last(), Some(&Op::Focus(WindowId(1))));
+}
+
+#[test]
+fn workspace_size_change_retiles() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    let mut config = wm.config().clone();
+    config.workspace_size = (1280, 720);
+    let changes = wm.processor.reconfigure(config).unwrap();
+    let rects = wm.state().tiled_rects(wm.config());
+    assert_eq!(moves(&changes), rects);
+    assert!(rects.iter().all(|(_, rect)| rect.x + rect.w <= 1280));
+
+    let mut config = wm.config().clone();
+    config.workspaces += 1;
+    assert!(wm.processor.reconfigure(config).is_err());
+}
+
+#[test]
+fn fullscreen_window_stays_put_when_others_open() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    let changes = wm.send("WS_FULLSCREEN");
+    assert!(changes.ops.contains(&Op::Fullscreen(
+        WindowId(1),
+        Rect::from_size(wm.config().workspace_size)
+    )));
+
+    let changes = wm.open("0x2");
+    assert!(!changes
+        .ops
+        .iter()
+        .any(|op| matches!(op, Op::Move(WindowId(1), _) | Op::Fullscreen(..))));
+}
+
+#[test]
+fn ignored_events_change_nothing() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    let changes = wm.send("CREATE 0x2");
+    assert!(changes.ops.is_empty(), "{:?}", changes.ops);
+    let changes = wm.send("WS_FOCUS 1");
+    assert!(changes.ops.is_empty());
+}
+
+#[test]
+fn dock_struts_follow_property_changes() {
+    let panel = Struts {
+        top: 20,
+        ..Struts::default()
+    };
+    let backend = FakeBackend {
+        docks: vec![(WindowId(9), panel)],
+        ..FakeBackend::default()
+    };
+    let mut wm = Wm::with_backend(Config::default(), backend);
+    wm.open("0x9");
+    wm.open("0x1");
+    assert_eq!(wm.state().usable_area(wm.config()).y, 20);
+
+    let taller = Struts {
+        top: 60,
+        ..Struts::default()
+    };
+    let changes = wm.process(Event::Client(ClientEvent::Struts(
+        WindowId(9),
+        Some(taller),
+    )));
+    let area = wm.state().usable_area(wm.config());
+    assert_eq!(area.y, 60);
+    assert_eq!(
+        moves(&changes),
+        vec![(WindowId(1), area.shrink(wm.config().gaps as i32))]
+    );
+
+    // Managed windows don't reserve anything
+    let changes = wm.process(Event::Client(ClientEvent::Struts(
+        WindowId(1),
+        Some(taller),
+    )));
+    assert!(changes.mutations.is_empty());
+
+    wm.process(Event::Client(ClientEvent::Struts(WindowId(9), None)));
+    assert_eq!(
+        wm.state().usable_area(wm.config()),
+        Rect::from_size(wm.config().workspace_size).reserve(wm.config().reserved)
+    );
+}
+
+#[test]
+fn window_unfullscreened_in_the_background_gets_its_border_back() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    wm.send("FOCUS_IN 0x1");
+    wm.send("WS_FULLSCREEN");
+    wm.send("FOCUS_IN 0x2");
+    wm.send("WS_FOCUS 2");
+    let unfullscreen = ClientEvent::Fullscreen(WindowId(1), StateAction::Remove);
+    let changes = wm.process(Event::Client(unfullscreen));
+    assert!(changes.ops.is_empty());
+    assert_eq!(wm.fullscreen(0), None);
+
+    let changes = wm.send("WS_FOCUS 1");
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+    assert!(changes
+        .ops
+        .contains(&Op::Border(WindowId(1), Border::Unfocused)));
+    assert!(changes
+        .ops
+        .contains(&Op::Border(WindowId(2), Border::Focused)));
+    assert!(!changes
+        .ops
+        .contains(&Op::Border(WindowId(2), Border::Unfocused)));
+}
@@ -0,0 +1,326 @@
+mod common;
+
+use common::{FakeBackend, Wm};
+use derpywm::geometry::Anchor;
+use derpywm::reconciler::Op;
+use derpywm::{Config, ScratchpadConfig, WindowId};
+
+#[test]
+fn shown_scratchpad_follows_workspace_switch() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open_scratchpad("0x2", "term");
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+
+    wm.send("WS_FOCUS 2");
+    assert_eq!(wm.state().scratchpad.shown, vec!["term"]);
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.focus(), None);
+    wm.send("WS_FOCUS 1");
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+}
+
+#[test]
+fn hiding_scratchpad_focuses_current_workspace() {
+    let mut wm = Wm::new();
+    wm.open_scratchpad("0x1", "term");
+    wm.open("0x2");
+    wm.send("WS_FOCUS 2");
+    wm.open("0x3");
+    wm.send("SP_TOGGLE term");
+    wm.send("WS_FOCUS 1");
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+}
+
+#[test]
+fn hiding_top_scratchpad_focuses_next_visible_one() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open_scratchpad("0x2", "notes");
+    wm.open_scratchpad("0x3", "calc");
+    wm.send("SP_TOGGLE notes");
+    wm.send("SP_TOGGLE calc");
+    assert_eq!(wm.focus(), Some(WindowId(3)));
+
+    // Toggling a covered scratchpad raises it instead of hiding it
+    wm.send("SP_TOGGLE notes");
+    assert_eq!(wm.state().scratchpad.shown, vec!["calc", "notes"]);
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+
+    wm.send("SP_TOGGLE notes");
+    assert_eq!(wm.focus(), Some(WindowId(3)));
+    wm.send("SP_TOGGLE calc");
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+}
+
+#[test]
+fn scratchpad_is_shown_over_fullscreen_window() {
+    let mut wm = Wm::new();
+    wm.open_scratchpad("0x1", "term");
+    wm.open("0x2");
+    wm.send("WS_FULLSCREEN");
+    assert_eq!(wm.fullscreen(0), Some(WindowId(2)));
+
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+    assert_eq!(wm.fullscreen(0), Some(WindowId(2)));
+
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+    assert_eq!(wm.fullscreen(0), Some(WindowId(2)));
+}
+
+#[test]
+fn switching_to_fullscreen_workspace_keeps_scratchpad_focused() {
+    let mut wm = Wm::new();
+    wm.open_scratchpad("0x1", "term");
+    wm.send("WS_FOCUS 2");
+    wm.open("0x2");
+    wm.send("WS_FULLSCREEN");
+    wm.send("WS_FOCUS 1");
+    wm.send("SP_TOGGLE term");
+
+    wm.send("WS_FOCUS 2");
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+    assert_eq!(wm.fullscreen(1), Some(WindowId(2)));
+}
+
+#[test]
+fn fullscreen_scratchpad_is_unfullscreened_when_left_behind() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open_scratchpad("0x2", "term");
+    wm.send("SP_TOGGLE term");
+    wm.send("WS_FULLSCREEN");
+    assert_eq!(wm.fullscreen(0), Some(WindowId(2)));
+
+    wm.send("WS_FOCUS 2");
+    assert_eq!(wm.fullscreen(0), None);
+    assert_eq!(wm.state().scratchpad.shown, vec!["term"]);
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+
+    wm.send("WS_FOCUS 1");
+    assert_eq!(wm.fullscreen(0), None);
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+}
+
+#[test]
+fn hiding_fullscreen_scratchpad_unfullscreens_it() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open_scratchpad("0x2", "term");
+    wm.send("SP_TOGGLE term");
+    wm.send("WS_FULLSCREEN");
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.fullscreen(0), None);
+    assert_eq!(wm.focus(), Some(WindowId(1)));
+}
+
+#[test]
+fn every_switch_toggle_fullscreen_sequence_keeps_focus_defined() {
+    let actions = [
+        "WS_FOCUS 1",
+        "WS_FOCUS 2",
+        "SP_TOGGLE term",
+        "WS_FULLSCREEN",
+    ];
+    for first in &actions {
+        for second in &actions {
+            for third in &actions {
+                let mut wm = Wm::new();
+                wm.open("0x1");
+                wm.open_scratchpad("0x2", "term");
+                wm.send("WS_FOCUS 2");
+                wm.open("0x3");
+                wm.send("WS_FOCUS 1");
+                for action in &[first, second, third] {
+                    wm.send(action);
+                }
+                let ws = wm.state().focused_workspace;
+                let expected = if !wm.state().scratchpad.shown.is_empty() {
+                    Some(WindowId(2))
+                } else if let Some(fullscreen) = wm.fullscreen(ws) {
+                    Some(fullscreen)
+                } else {
+                    Some([WindowId(1), WindowId(3)][ws])
+                };
+                assert_eq!(
+                    wm.focus(),
+                    expected,
+                    "after {}, {}, {}: {:?}",
+                    first,
+                    second,
+                    third,
+                    wm.state()
+                );
+                // A fullscreen scratchpad only exists while it's shown
+                for workspace in &wm.state().workspaces {
+                    if workspace.fullscreen == Some(WindowId(2)) {
+                        assert_eq!(wm.state().scratchpad.shown, vec!["term"]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn reused_name_returns_old_window_to_workspace() {
+    let mut wm = Wm::new();
+    wm.open_scratchpad("0x1", "term");
+    wm.send("SP_TOGGLE term");
+    wm.open("0x2");
+    wm.send("FOCUS_IN 0x2");
+    wm.send("SP_ADD term");
+    assert_eq!(
+        wm.state().scratchpad.windows,
+        vec![("term".into(), WindowId(2))]
+    );
+    assert!(wm.state().scratchpad.shown.is_empty());
+    assert_eq!(wm.state().workspaces[0].windows, vec![WindowId(1)]);
+}
+
+#[test]
+fn restore_all_returns_scratchpads_to_focused_workspace() {
+    let mut wm = Wm::new();
+    wm.open_scratchpad("0x1", "notes");
+    wm.open_scratchpad("0x2", "calc");
+    wm.send("SP_TOGGLE calc");
+    wm.send("WS_FOCUS 3");
+    wm.send("SP_RESTORE_ALL");
+    assert!(wm.state().scratchpad.windows.is_empty());
+    assert!(wm.state().scratchpad.shown.is_empty());
+    assert_eq!(
+        wm.state().workspaces[2].windows,
+        vec![WindowId(1), WindowId(2)]
+    );
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+}
+
+/// Wm with a `term` scratchpad launched as `xterm -name dropdown`.
+fn with_launchable_term() -> Wm {
+    let mut config = Config::default();
+    config.scratchpads.push(ScratchpadConfig {
+        name: "term".to_string(),
+        command: "xterm -name dropdown".to_string(),
+        class: "dropdown".to_string(),
+        size: None,
+        anchor: Anchor::Center,
+    });
+    let backend = FakeBackend {
+        classes: vec![(WindowId(7), "Dropdown".to_string())],
+        ..FakeBackend::default()
+    };
+    Wm::with_backend(config, backend)
+}
+
+#[test]
+fn toggling_unknown_scratchpad_launches_its_command() {
+    let mut wm = with_launchable_term();
+    wm.open("0x1");
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.backend().spawned, vec!["xterm -name dropdown"]);
+    assert_eq!(wm.state().scratchpad.pending, vec!["term"]);
+    assert!(wm.try_send("SP_TOGGLE nope").is_err());
+}
+
+#[test]
+fn launched_window_is_captured_and_shown_when_it_maps() {
+    let mut wm = with_launchable_term();
+    wm.open("0x1");
+    wm.send("SP_TOGGLE term");
+    // Windows of other classes still go to the workspace
+    wm.open("0x2");
+    assert_eq!(wm.windows(0), vec![WindowId(1), WindowId(2)]);
+
+    wm.open("0x7");
+    assert!(wm.state().scratchpad.pending.is_empty());
+    assert_eq!(wm.state().scratchpad.window("term"), Some(WindowId(7)));
+    assert_eq!(wm.state().scratchpad.shown, vec!["term"]);
+    assert_eq!(wm.focus(), Some(WindowId(7)));
+    assert_eq!(wm.windows(0), vec![WindowId(1), WindowId(2)]);
+
+    wm.send("SP_TOGGLE term");
+    assert!(wm.state().scratchpad.shown.is_empty());
+    assert_eq!(wm.backend().spawned.len(), 1);
+}
+
+#[test]
+fn toggling_again_while_launching_gives_up_waiting() {
+    let mut wm = with_launchable_term();
+    wm.send("SP_TOGGLE term");
+    wm.send("SP_TOGGLE term");
+    assert!(wm.state().scratchpad.pending.is_empty());
+    assert_eq!(wm.backend().spawned.len(), 1);
+
+    // The window that never showed up no longer blocks launching it again
+    wm.send("SP_TOGGLE term");
+    assert_eq!(wm.backend().spawned.len(), 2);
+    assert_eq!(wm.state().scratchpad.pending, vec!["term"]);
+}
+
+/// Windows raised by `ops`, in order.
+fn raised(ops: &[Op]) -> Vec<WindowId> {
+    ops.iter()
+        .filter_map(|op| match op {
+            Op::Raise(wid) => Some(*wid),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn shown_scratchpads_stay_mapped_and_stack_in_toggle_order() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open_scratchpad("0x2", "notes");
+    wm.open_scratchpad("0x3", "calc");
+    wm.send("SP_TOGGLE notes");
+    let changes = wm.send("SP_TOGGLE calc");
+    assert!(changes.ops.contains(&Op::Map(WindowId(3))));
+    assert!(!changes.ops.contains(&Op::Unmap(WindowId(2))));
+    assert_eq!(raised(&changes.ops).last(), Some(&WindowId(3)));
+
+    let changes = wm.send("SP_TOGGLE notes");
+    assert_eq!(raised(&changes.ops).last(), Some(&WindowId(2)));
+    assert!(!changes.ops.contains(&Op::Unmap(WindowId(3))));
+
+    // Switching workspaces keeps both over it in the same order
+    let changes = wm.send("WS_FOCUS 2");
+    let order = raised(&changes.ops);
+    let notes = order.iter().rposition(|wid| *wid == WindowId(2));
+    let calc = order.iter().rposition(|wid| *wid == WindowId(3));
+    assert!(calc.is_some() && calc < notes, "{:?}", order);
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+}
+
+#[test]
+fn losing_a_shown_scratchpad_keeps_the_others_stacked() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open_scratchpad("0x2", "notes");
+    wm.open_scratchpad("0x3", "calc");
+    wm.open_scratchpad("0x4", "music");
+    for name in &["notes", "calc", "music"] {
+        wm.send(&format!("SP_TOGGLE {}", name));
+    }
+
+    wm.send("DESTROY 0x3");
+    assert_eq!(wm.state().scratchpad.shown, vec!["notes", "music"]);
+    assert_eq!(wm.focus(), Some(WindowId(4)));
+
+    wm.send("SP_REMOVE music");
+    assert_eq!(wm.state().scratchpad.shown, vec!["notes"]);
+    assert_eq!(wm.windows(0), vec![WindowId(1), WindowId(4)]);
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+
+    wm.send("SP_TOGGLE notes");
+    assert!(wm.state().scratchpad.shown.is_empty());
+    assert_eq!(wm.focus(), Some(WindowId(4)));
+}
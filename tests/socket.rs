@@ -0,0 +1,53 @@
+use derpywm::processor::{EventProcessor, HeadlessBackend};
+use derpywm::socket;
+use derpywm::Config;
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc;
+use std::thread;
+
+#[test]
+fn clients_get_answers_on_their_connection() {
+    let path = std::env::temp_dir().join(format!("derpy-wm-{}.sock", std::process::id()));
+    let (sender, events) = mpsc::channel();
+    socket::listen(&path, sender).unwrap();
+
+    // Stands in for the main loop
+    thread::spawn(move || {
+        let mut processor = EventProcessor::new(Config::default(), HeadlessBackend);
+        for (event, reply) in events {
+            let answer = match processor.process(event) {
+                Ok(changes) => changes.reply.unwrap_or_default(),
+                Err(err) => format!("error: {}", err),
+            };
+            if let Some(reply) = reply {
+                reply.send(answer).unwrap();
+            }
+        }
+    });
+
+    let mut client = UnixStream::connect(&path).unwrap();
+    client
+        .write_all(b"WS_FOCUS 2\nWS_FOCUS 99\nQUERY_STATE\n")
+        .unwrap();
+    client.shutdown(Shutdown::Write).unwrap();
+    let mut answer = String::new();
+    client.read_to_string(&mut answer).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut lines = answer.lines();
+    assert_eq!(lines.next(), Some("error: there is no workspace 99"));
+    assert_eq!(lines.next(), Some("focused_workspace 2"));
+    assert!(answer.contains("\nurgent"));
+}
+
+#[test]
+fn stale_socket_is_replaced() {
+    let path = std::env::temp_dir().join(format!("derpy-wm-stale-{}.sock", std::process::id()));
+    std::fs::write(&path, "").unwrap();
+    let (sender, _events) = mpsc::channel();
+    socket::listen(&path, sender).unwrap();
+    assert!(UnixStream::connect(&path).is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
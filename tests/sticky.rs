@@ -0,0 +1,86 @@
+mod common;
+
+use common::Wm;
+use derpywm::reconciler::Op;
+use derpywm::WindowId;
+
+/// 0x1 tiled on the first workspace and 0x2 sticky.
+fn with_sticky() -> Wm {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.open("0x2");
+    wm.send("WINDOW_STICKY_TOGGLE");
+    wm
+}
+
+#[test]
+fn sticky_toggle_takes_the_window_off_its_workspace() {
+    let mut wm = with_sticky();
+    assert_eq!(wm.windows(0), vec![WindowId(1)]);
+    let area = wm.state().usable_area(wm.config());
+    let rect = area.centered(wm.config().scratchpad_size);
+    assert_eq!(wm.state().sticky, vec![(WindowId(2), rect)]);
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+
+    wm.send("WINDOW_STICKY_TOGGLE");
+    assert!(wm.state().sticky.is_empty());
+    assert_eq!(wm.windows(0), vec![WindowId(1), WindowId(2)]);
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+}
+
+#[test]
+fn floating_window_keeps_its_geometry_when_sticky() {
+    let mut wm = Wm::new();
+    wm.open("0x1");
+    wm.send("WINDOW_FLOAT_TOGGLE");
+    let rect = wm.state().workspaces[0].floating_rect(WindowId(1));
+    wm.send("WINDOW_STICKY_TOGGLE");
+    assert_eq!(wm.state().sticky_rect(WindowId(1)), rect);
+    assert!(wm.state().workspaces[0].floating.is_empty());
+}
+
+#[test]
+fn sticky_window_stays_shown_across_workspaces() {
+    let mut wm = with_sticky();
+    let changes = wm.send("WS_FOCUS 2");
+    assert!(changes.ops.contains(&Op::Unmap(WindowId(1))));
+    assert!(!changes.ops.contains(&Op::Unmap(WindowId(2))));
+    assert!(changes.ops.contains(&Op::Raise(WindowId(2))));
+    assert_eq!(wm.focus(), Some(WindowId(2)));
+
+    // New windows tile below it and it stays above them
+    wm.open("0x3");
+    let changes = wm.send("WS_FOCUS 1");
+    assert!(changes.ops.contains(&Op::Raise(WindowId(2))));
+    assert!(!changes.ops.contains(&Op::Unmap(WindowId(2))));
+
+    // Unsticking puts it on the workspace it is on now
+    wm.send("WS_FOCUS 2");
+    wm.send("FOCUS_IN 0x2");
+    wm.send("WINDOW_STICKY_TOGGLE");
+    assert_eq!(wm.windows(0), vec![WindowId(1)]);
+    assert_eq!(wm.windows(1), vec![WindowId(3), WindowId(2)]);
+}
+
+#[test]
+fn destroyed_sticky_window_is_forgotten() {
+    let mut wm = with_sticky();
+    wm.send("DESTROY 0x2");
+    assert!(wm.state().sticky.is_empty());
+    assert_ne!(wm.state().focused_window, Some(WindowId(2)));
+    assert!(wm.state().check_invariants().is_ok());
+
+    let changes = wm.send("WS_FOCUS 2");
+    assert!(!changes.ops.contains(&Op::Raise(WindowId(2))));
+}
+
+#[test]
+fn sticky_window_isnt_made_fullscreen() {
+    let mut wm = with_sticky();
+    assert!(wm.send("WS_FULLSCREEN").mutations.is_empty());
+    assert_eq!(wm.fullscreen(0), None);
+
+    wm.send("FOCUS_IN 0x1");
+    wm.send("WS_FULLSCREEN");
+    assert_eq!(wm.fullscreen(0), Some(WindowId(1)));
+}